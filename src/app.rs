@@ -1,45 +1,283 @@
 use chrono::prelude::*;
 use chrono::Duration;
 
-use ratatui::style::{Color};
-use ratatui::widgets::{ScrollbarState, TableState};
-use sqlite::State;
+use ratatui::widgets::{ListState, ScrollbarState, TableState};
+use sqlite::{Connection, State, Statement, Value};
+
+use crate::audit::{self, AuditEntry};
+use crate::column::ColumnSettings;
+use crate::currency::{self, home_price_sql};
+use crate::db;
+use crate::import::ImportRow;
+use crate::money::Money;
+use crate::pricing::{self, SAVINGS_SQL};
+use crate::query::{self, Filter};
+use crate::sort::Sort;
+use crate::view::{self, View};
+
+/// Rows fetched from the database at a time while scrolling
+const ROW_WINDOW: usize = 200;
+
+/// Meal plan terms, saved per profile in the setting table
+pub struct MealPlan {
+    /// Price charged for a single meal swipe.
+    pub swipe_price: Money,
+    /// Swipes assumed to be used each day of the semester.
+    pub swipes_per_day: i64,
+    /// Swipes included in the meal plan for one semester.
+    pub semester_swipes: i64,
+    pub semester_start: NaiveDate,
+    pub semester_end: NaiveDate,
+}
+
+impl Default for MealPlan {
+    fn default() -> MealPlan {
+        MealPlan {
+            swipe_price: Money::from_cents(1512),
+            swipes_per_day: 2,
+            semester_swipes: 210,
+            semester_start: NaiveDate::from_ymd_opt(2024, 9, 1).unwrap(),
+            semester_end: NaiveDate::from_ymd_opt(2024, 12, 20).unwrap(),
+        }
+    }
+}
+
+impl MealPlan {
+    // Current profile's plan, falling back to the defaults for anything never set. The settings
+    // are read in one query as the statistics load the plan several times per frame
+    pub fn load() -> MealPlan {
+        let default = MealPlan::default();
+        let settings = db::settings();
+        let setting = |key: &str| settings.get(key).map(String::as_str);
+        let date = |key: &str, default: NaiveDate| {
+            setting(key)
+                .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+                .unwrap_or(default)
+        };
+
+        MealPlan {
+            swipe_price: setting("swipe_price")
+                .and_then(|value| Money::parse(value).ok())
+                .unwrap_or(default.swipe_price),
+            swipes_per_day: setting("swipes_per_day")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.swipes_per_day),
+            semester_swipes: setting("semester_swipes")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.semester_swipes),
+            semester_start: date("semester_start", default.semester_start),
+            semester_end: date("semester_end", default.semester_end),
+        }
+    }
+
+    pub fn save(&self) {
+        db::set_setting("swipe_price", &self.swipe_price.plain());
+        db::set_setting("swipes_per_day", &self.swipes_per_day.to_string());
+        db::set_setting("semester_swipes", &self.semester_swipes.to_string());
+        db::set_setting("semester_start", &self.semester_start.to_string());
+        db::set_setting("semester_end", &self.semester_end.to_string());
+    }
+}
+
+// Splits a comma separated list of names, dropping blanks
+pub fn parse_names(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Expands the "t" (today) and "y" (yesterday) shortcuts accepted by date inputs
+pub fn resolve_date_input(input: &str) -> String {
+    match input.to_lowercase().as_str() {
+        "t" => format!("{}", Local::now().format("%Y-%m-%d")),
+        "y" => format!("{}", (Local::now() - Duration::days(1)).format("%Y-%m-%d")),
+        _ => input.to_string(),
+    }
+}
 
 pub enum CurrentScreen {
     Main,
     SingleInput,
+    #[allow(dead_code)]
     RemoveConfirmation,
     EditExpended,
-    Query
+    Query,
+    Analysis,
+    DiningInput,
+    DiningBalance,
+    Templates,
+    TemplateInput,
+    RecurringConfirm,
+    ShoppingList,
+    ShoppingInput,
+    ImportPreview,
+    AuditLog,
+    SettleUp,
+    Profiles,
+    ProfileInput,
+    StorePrices,
+    Views,
+    ViewInput,
+    Columns,
 }
 
 pub enum ItemInfo {
+    #[allow(dead_code)]
     Id,
     Ingredient,
     Price,
+    OriginalPrice,
+    Discount,
+    TaxRate,
+    Currency,
+    Store,
     ExpendedDate,
     PurchaseDate,
+    PaidBy,
+    SplitBetween,
+    Location,
+    Amount,
+    TransactionDate,
+    TemplateName,
+    TemplateItems,
+    TemplateEvery,
+    ShoppingItem,
+    ProfileName,
+    ViewName,
 }
 
-#[derive(Debug)]
-#[derive(Clone)]
-pub struct DbResults {
-    id: i32,
-    ingredient: String,
-    price: i32,
-    expended_date: String,
-    purchase_date: String,
+/// A purchase row as read from the database, formatted only when drawn or exported
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: i64,
+    pub ingredient: String,
+    /// Price in `currency` as entered, before any discount and tax
+    pub price: Money,
+    /// None for the home currency
+    pub currency: Option<String>,
+    /// Regular shelf price when the item was bought on sale
+    pub original_price: Option<Money>,
+    /// Coupon or discount taken off the price
+    pub discount: Option<Money>,
+    /// Tax in percent, charged on the price less the discount
+    pub tax_rate: Option<f64>,
+    /// Net price converted to the home currency at the current exchange rate
+    pub home_price: Money,
+    pub store: Option<String>,
+    /// Roommate who paid
+    pub paid_by: Option<String>,
+    /// Everyone sharing the cost, empty when the payer keeps it to themselves
+    pub split_between: Vec<String>,
+    pub purchase_date: Option<NaiveDate>,
+    /// None while the item is still in the pantry
+    pub expended_date: Option<NaiveDate>,
+    /// Dates as stored, so exports can keep text that does not parse
+    pub purchase_date_text: Option<String>,
+    pub expended_date_text: Option<String>,
+}
+
+impl Transaction {
+    // Reads the current row of a `SELECT rowid, *, ... AS homePrice FROM purchase` statement
+    pub fn read(statement: &Statement) -> sqlite::Result<Transaction> {
+        // Unexpended items store the text 'NULL', which fails to parse like any other bad date
+        let purchase_date_text = statement.read::<Option<String>, _>("purchaseDate")?;
+        let expended_date_text = statement.read::<Option<String>, _>("expendedDate")?;
+        let date = |text: &Option<String>| {
+            text.as_deref()
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        };
+
+        Ok(Transaction {
+            id: statement.read::<i64, _>("rowid")?,
+            ingredient: statement
+                .read::<Option<String>, _>("ingredient")?
+                .unwrap_or_default(),
+            price: Money::from_cents(statement.read::<i64, _>("price").unwrap_or(0)),
+            currency: statement.read::<Option<String>, _>("currency")?,
+            original_price: statement
+                .read::<Option<i64>, _>("originalPrice")?
+                .map(Money::from_cents),
+            discount: statement
+                .read::<Option<i64>, _>("discount")?
+                .map(Money::from_cents),
+            tax_rate: statement.read::<Option<f64>, _>("taxRate")?,
+            home_price: Money::from_cents(statement.read::<i64, _>("homePrice").unwrap_or(0)),
+            store: statement.read::<Option<String>, _>("store")?,
+            paid_by: statement.read::<Option<String>, _>("paidBy")?,
+            split_between: parse_names(
+                &statement
+                    .read::<Option<String>, _>("splitBetween")?
+                    .unwrap_or_default(),
+            ),
+            purchase_date: date(&purchase_date_text),
+            expended_date: date(&expended_date_text),
+            purchase_date_text,
+            expended_date_text,
+        })
+    }
+
+    // What was paid after the discount and tax, in `currency`
+    pub fn net_price(&self) -> Money {
+        pricing::net_price(self.price, self.discount, self.tax_rate)
+    }
+
+    // Saved through sales and coupons, in `currency`
+    pub fn savings(&self) -> Money {
+        pricing::savings(self.price, self.original_price, self.discount)
+    }
+
+    // Net price with the currency symbol, or the currency code for purchases made abroad
+    pub fn price_text(&self) -> String {
+        self.amount_text(self.net_price())
+    }
+
+    // An amount in this purchase's currency
+    pub fn amount_text(&self, amount: Money) -> String {
+        match &self.currency {
+            Some(code) => amount.in_currency(code),
+            None => amount.to_string(),
+        }
+    }
+
+    pub fn days_lasted(&self) -> Option<i64> {
+        Some((self.expended_date? - self.purchase_date?).num_days())
+    }
+
+    pub fn purchase_text(&self) -> String {
+        self.purchase_date
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    pub fn expended_text(&self) -> String {
+        self.expended_date
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "NULL".to_string())
+    }
 }
 
 // #[derive(Debug, Default)]
-pub struct App{
+pub struct App {
     /// Is the application running?
+    #[allow(dead_code)]
     pub running: bool,
-    pub order_by: String,
-    pub search_param: String,
+    pub sort: Sort,
+    pub filter: Filter,
+    /// Why the search input could not be parsed, the previous filter stays active meanwhile
+    pub query_error: Option<String>,
     pub state: TableState,
-    pub item_count: i32,
-    pub row_data: Vec<Vec<String>>,
+    /// Rows matching the search, `row_data` only caches a window of them
+    pub item_count: usize,
+    /// Position of `row_data[0]` among all matching rows
+    pub row_offset: usize,
+    /// Search and sort the cached rows were loaded with, None to reload on the next draw
+    pub rows_key: Option<String>,
+    /// Table rows visible at once, used by PageUp/PageDown
+    pub page_height: usize,
+    pub row_data: Vec<Transaction>,
     // pub single_insert_mode: bool,
     pub currently_editing: Option<ItemInfo>,
     pub current_screen: CurrentScreen,
@@ -47,26 +285,212 @@ pub struct App{
     pub price_input: String,
     pub expended_date_input: String,
     pub query_input: String,
+    pub status_message: String,
     pub purchase_date_input: String,
-    pub scroll_state: ScrollbarState
+    pub paid_by_input: String,
+    pub split_between_input: String,
+    pub store_input: String,
+    /// Currency code for new purchases, blank for the home currency
+    pub currency_input: String,
+    pub original_price_input: String,
+    pub discount_input: String,
+    pub tax_rate_input: String,
+    pub servings_input: String,
+    pub location_input: String,
+    pub amount_input: String,
+    pub transaction_date_input: String,
+    pub dining_balance_input: String,
+    pub templates: Vec<Template>,
+    pub template_state: ListState,
+    pub due_templates: Vec<Template>,
+    pub template_name_input: String,
+    pub template_items_input: String,
+    pub template_every_input: String,
+    pub shopping_list: Vec<ShoppingItem>,
+    pub shopping_state: ListState,
+    pub shopping_input: String,
+    pub import_rows: Vec<ImportRow>,
+    pub audit_entries: Vec<AuditEntry>,
+    pub audit_state: TableState,
+    pub profiles: Vec<String>,
+    pub profile_state: ListState,
+    pub profile_input: String,
+    pub views: Vec<View>,
+    pub view_state: ListState,
+    pub view_input: String,
+    pub columns: ColumnSettings,
+    pub column_state: ListState,
+    pub scroll_state: ScrollbarState,
+}
+
+/// A purchase about to be inserted, price in cents and dates already resolved
+pub struct NewPurchase {
+    pub ingredient: String,
+    pub price: Money,
+    pub purchase_date: String,
+    pub expended_date: String,
+    pub paid_by: Option<String>,
+    pub store: Option<String>,
+    /// None for the home currency
+    pub currency: Option<String>,
+    pub original_price: Option<Money>,
+    pub discount: Option<Money>,
+    /// Percent, None when the price already includes tax
+    pub tax_rate: Option<f64>,
+    /// Everyone sharing the cost, empty when the payer keeps it to themselves
+    pub split_between: Vec<String>,
+}
+
+impl NewPurchase {
+    pub fn new(
+        ingredient: &str,
+        price: Money,
+        purchase_date: &str,
+        expended_date: &str,
+    ) -> NewPurchase {
+        NewPurchase {
+            ingredient: ingredient.to_string(),
+            price,
+            purchase_date: purchase_date.to_string(),
+            expended_date: expended_date.to_string(),
+            paid_by: None,
+            store: None,
+            currency: None,
+            original_price: None,
+            discount: None,
+            tax_rate: None,
+            split_between: Vec::new(),
+        }
+    }
 }
 
 pub struct MealSwipeInfo {
     pub swipes: i64,
-    pub cost: f64,
+    pub cost: Money,
+}
+
+#[derive(Clone)]
+pub struct Template {
+    pub name: String,
+    pub ingredients: Vec<String>,
+    /// Days between recurrences, `None` for templates that are only applied by hand
+    pub every_days: Option<i64>,
+    pub last_applied: Option<NaiveDate>,
+}
+
+impl Template {
+    pub fn is_due(&self) -> bool {
+        match (self.every_days, self.last_applied) {
+            (Some(every_days), Some(last_applied)) => {
+                Local::now().date_naive() >= last_applied + Duration::days(every_days)
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+pub struct ShoppingItem {
+    pub ingredient: String,
+    /// Last price before any discount or tax, `None` for items never bought before
+    pub estimated_price: Option<Money>,
+    /// Currency of `estimated_price`, None for the home currency
+    pub currency: Option<String>,
 }
 
+impl ShoppingItem {
+    pub fn new(ingredient: &str, last_price: Option<(Money, Option<String>)>) -> ShoppingItem {
+        let (estimated_price, currency) = match last_price {
+            Some((price, currency)) => (Some(price), currency),
+            None => (None, None),
+        };
+        ShoppingItem {
+            ingredient: ingredient.to_string(),
+            estimated_price,
+            currency,
+        }
+    }
+
+    pub fn price_text(&self) -> String {
+        match (self.estimated_price, &self.currency) {
+            (Some(price), Some(code)) => price.in_currency(code),
+            (Some(price), None) => price.to_string(),
+            (None, _) => "?".to_string(),
+        }
+    }
+
+    // Estimated price in the home currency, for totalling the list
+    pub fn home_price(&self) -> Option<Money> {
+        Some(currency::to_home(
+            self.estimated_price?,
+            self.currency.as_deref(),
+        ))
+    }
+}
+
+/// Average price of one ingredient at one store
+pub struct StorePrice {
+    pub ingredient: String,
+    pub store: String,
+    pub average_price: Money,
+    pub purchases: i64,
+    /// Whether no other store sells this ingredient for less on average
+    pub cheapest: bool,
+}
+
+pub struct DiningInfo {
+    pub starting_balance: Money,
+    pub remaining: Money,
+    /// Remaining balance spread evenly over the days left in the semester
+    pub daily_allowance: Money,
+    /// When the balance runs out at the spending pace so far, None if it lasts the semester
+    pub run_out_date: Option<NaiveDate>,
+}
+
+pub struct SwipeForecast {
+    /// Swipes recorded since the semester started
+    pub swipes_used: i64,
+    pub swipes_remaining: i64,
+    /// Recorded swipes per day so far, None until a swipe is recorded
+    pub burn_rate: Option<f64>,
+    /// Swipes left at semester end at the current burn rate (negative if they run out early)
+    pub projected_leftover: i64,
+    pub run_out_date: Option<NaiveDate>,
+    /// Swipes per week that would use the remaining balance exactly by semester end
+    pub recommended_per_week: f64,
+}
+
+pub struct BreakEvenInfo {
+    pub grocery_spend: Money,
+    /// Home-cooked servings recorded this semester
+    pub home_meals: i64,
+    /// None until home-cooked meals are recorded
+    pub cost_per_home_meal: Option<Money>,
+    pub swipe_price: Money,
+    /// Saved per meal by cooking instead of swiping (negative if cooking costs more)
+    pub savings_per_meal: Option<Money>,
+    /// Swipes the semester leaves unused at the recorded pace, plus one a week swapped for
+    /// cooking when cooking is cheaper. None until swipes and meals are recorded
+    pub swipes_to_drop: Option<i64>,
+    pub drop_savings: Money,
+    /// Saved over a semester by replacing one swipe a week with a home-cooked meal
+    pub weekly_swap_savings: Option<Money>,
+}
 
 impl App {
     /// Construct a new instance of [`App`].
     pub fn new() -> App {
         App {
             running: false,
-            order_by: "expendedDate DESC, purchaseDate ASC".to_string(),
-            search_param: "true".to_string(),
+            sort: Sort::default(),
+            filter: Filter::default(),
+            query_error: None,
             state: TableState::default().with_selected(0),
             item_count: 0,
-            row_data: Vec::<Vec<String>>::new(),
+            row_offset: 0,
+            rows_key: None,
+            page_height: 0,
+            row_data: Vec::<Transaction>::new(),
             // single_insert_mode: true,
             currently_editing: None,
             scroll_state: ScrollbarState::new(1),
@@ -74,148 +498,985 @@ impl App {
             ingredient_input: String::new(),
             price_input: String::new(),
             query_input: String::new(),
+            status_message: String::new(),
             expended_date_input: String::new(),
             purchase_date_input: String::new(),
+            paid_by_input: String::new(),
+            store_input: String::new(),
+            currency_input: String::new(),
+            original_price_input: String::new(),
+            discount_input: String::new(),
+            tax_rate_input: String::new(),
+            split_between_input: String::new(),
+            servings_input: String::new(),
+            location_input: String::new(),
+            amount_input: String::new(),
+            transaction_date_input: String::new(),
+            dining_balance_input: String::new(),
+            templates: Vec::new(),
+            template_state: ListState::default().with_selected(Some(0)),
+            due_templates: Vec::new(),
+            template_name_input: String::new(),
+            template_items_input: String::new(),
+            template_every_input: String::new(),
+            shopping_list: Vec::new(),
+            shopping_state: ListState::default().with_selected(Some(0)),
+            shopping_input: String::new(),
+            import_rows: Vec::new(),
+            audit_entries: Vec::new(),
+            audit_state: TableState::default().with_selected(0),
+            profiles: Vec::new(),
+            profile_state: ListState::default().with_selected(Some(0)),
+            profile_input: String::new(),
+            views: Vec::new(),
+            view_state: ListState::default().with_selected(Some(0)),
+            view_input: String::new(),
+            columns: ColumnSettings::load(),
+            column_state: ListState::default().with_selected(Some(0)),
         }
     }
 
     pub fn submit_ingredient(&mut self) {
         // Send value to database
-        let conn = sqlite::open("src/purchases.db").unwrap();
-        let price = (&self.price_input.parse::<f64>().unwrap() * 100.0) as i64;
+        let price = Money::parse(&self.price_input).unwrap();
         let mut expended = String::from("NULL");
-        let mut purchase_date: String;
 
+        if !self.expended_date_input.is_empty() {
+            expended = resolve_date_input(&self.expended_date_input);
+        }
+        let purchase_date = resolve_date_input(&self.purchase_date_input);
 
-        if !&self.expended_date_input.is_empty() {
-            if self.expended_date_input.to_lowercase().eq("t") {
-                let today = Local::now();
-                expended = format!("{}",today.format("%Y-%m-%d"));
-            } else if self.expended_date_input.to_lowercase().eq("y") {
-                let yesterday = Local::now() - Duration::days(1);
-                expended = format!("{}", yesterday.format("%Y-%m-%d"));
-            } else {
-                expended = self.expended_date_input.clone();
-            }
+        let mut purchase =
+            NewPurchase::new(&self.ingredient_input, price, &purchase_date, &expended);
+        if !self.paid_by_input.trim().is_empty() {
+            purchase.paid_by = Some(self.paid_by_input.trim().to_string());
+        }
+        purchase.split_between = parse_names(&self.split_between_input);
+        if !self.store_input.trim().is_empty() {
+            purchase.store = Some(self.store_input.trim().to_string());
         }
+        purchase.currency = currency::for_purchase(&self.currency_input).unwrap();
+        purchase.original_price =
+            pricing::parse_optional_amount(&self.original_price_input).unwrap();
+        purchase.discount = pricing::parse_optional_amount(&self.discount_input).unwrap();
+        purchase.tax_rate = pricing::parse_tax_rate(&self.tax_rate_input).unwrap();
 
-        if self.purchase_date_input.to_lowercase().eq("t") {
-            let today = Local::now();
-            purchase_date = format!("{}",today.format("%Y-%m-%d"));
-        } else if self.purchase_date_input.to_lowercase().eq("y") {
-            let yesterday = Local::now() - Duration::days(1);
-            purchase_date = format!("{}", yesterday.format("%Y-%m-%d"));
-        } else {
-            purchase_date = self.purchase_date_input.clone();
+        App::insert_purchase(&purchase);
+    }
+
+    // Adds a purchase row and registers anyone and any store it names
+    pub fn insert_purchase(purchase: &NewPurchase) {
+        let conn = db::open();
+        let transaction = db::Transaction::begin(&conn);
+        let store = purchase
+            .store
+            .as_deref()
+            .map(|store| App::register_store(&conn, store));
+        let query = "INSERT INTO purchase (ingredient, price, purchaseDate, expendedDate, paidBy, splitBetween, store, currency, originalPrice, discount, taxRate) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+        let mut statement = conn.prepare(query).unwrap();
+        let split_between = purchase.split_between.join(",");
+
+        statement.bind((1, purchase.ingredient.as_str())).unwrap();
+        statement.bind((2, purchase.price.cents())).unwrap();
+        statement
+            .bind((3, purchase.purchase_date.as_str()))
+            .unwrap();
+        statement
+            .bind((4, purchase.expended_date.as_str()))
+            .unwrap();
+        statement.bind((5, purchase.paid_by.as_deref())).unwrap();
+        statement
+            .bind((
+                6,
+                (!split_between.is_empty()).then_some(split_between.as_str()),
+            ))
+            .unwrap();
+        statement.bind((7, store.as_deref())).unwrap();
+        statement.bind((8, purchase.currency.as_deref())).unwrap();
+        statement
+            .bind((9, purchase.original_price.map(Money::cents)))
+            .unwrap();
+        statement
+            .bind((10, purchase.discount.map(Money::cents)))
+            .unwrap();
+        statement.bind((11, purchase.tax_rate)).unwrap();
+
+        statement.next().unwrap();
+
+        for name in purchase.paid_by.iter().chain(purchase.split_between.iter()) {
+            let mut statement = conn
+                .prepare("INSERT OR IGNORE INTO person (name) VALUES (?)")
+                .unwrap();
+            statement.bind((1, name.as_str())).unwrap();
+            statement.next().unwrap();
         }
 
+        let mut statement = conn.prepare("SELECT last_insert_rowid() AS id").unwrap();
+        statement.next().unwrap();
+        let id = statement.read::<i64, _>("id").unwrap();
+        audit::record(&conn, "insert", id, None, audit::snapshot(&conn, id));
+        transaction.commit();
+    }
 
+    // Adds the store if it is new, returning the name as first entered so "aldi" and "Aldi" stay one store
+    fn register_store(conn: &Connection, name: &str) -> String {
+        let mut statement = conn
+            .prepare("INSERT OR IGNORE INTO store (name) VALUES (?)")
+            .unwrap();
+        statement.bind((1, name)).unwrap();
+        statement.next().unwrap();
 
+        let mut statement = conn
+            .prepare("SELECT name FROM store WHERE name = ?")
+            .unwrap();
+        statement.bind((1, name)).unwrap();
+        statement.next().unwrap();
+        statement.read::<String, _>("name").unwrap()
+    }
 
-        let query = "INSERT INTO purchase (ingredient, price, purchaseDate, expendedDate) VALUES (?, ?, ?, ?)";
+    // Known stores, most used first
+    pub fn get_stores() -> Vec<String> {
+        let conn = db::open();
+        let query = "
+            SELECT name FROM store
+            ORDER BY (SELECT count(*) FROM purchase WHERE purchase.store = store.name) DESC, name
+        ";
         let mut statement = conn.prepare(query).unwrap();
+        let mut stores = Vec::new();
 
-        statement.bind((1, self.ingredient_input.as_str())).unwrap();
-        statement.bind((2, price)).unwrap();
-        statement.bind((3, purchase_date.as_str())).unwrap();
-        statement.bind((4, expended.as_str())).unwrap();
+        while let State::Row = statement.next().unwrap() {
+            stores.push(statement.read::<String, _>("name").unwrap());
+        }
 
-        statement.next().unwrap();
+        stores
     }
 
-    pub fn update_expended(item_id: String, new_date: String) {
-        let conn = sqlite::open("src/purchases.db").unwrap();
+    // First known store that completes the store input, used for the autocomplete hint
+    pub fn store_suggestion(&self) -> Option<String> {
+        let input = self.store_input.trim().to_lowercase();
+        if input.is_empty() {
+            return None;
+        }
+
+        App::get_stores()
+            .into_iter()
+            .find(|store| store.to_lowercase().starts_with(&input) && store.len() > input.len())
+    }
+
+    // Average price of every ingredient bought at more than one store
+    pub fn get_store_prices() -> Vec<StorePrice> {
+        let conn = db::open();
+        let query = format!(
+            "
+            SELECT ingredient, store, avg({}) AS average, count(*) AS purchases
+            FROM purchase
+            WHERE store IS NOT NULL AND lower(ingredient) IN (
+                SELECT lower(ingredient) FROM purchase
+                WHERE store IS NOT NULL
+                GROUP BY lower(ingredient)
+                HAVING count(DISTINCT store) > 1
+            )
+            GROUP BY lower(ingredient), store
+            ORDER BY lower(ingredient), average
+        ",
+            home_price_sql()
+        );
+        let mut statement = conn.prepare(query).unwrap();
+        let mut prices = Vec::<StorePrice>::new();
+
+        while let State::Row = statement.next().unwrap() {
+            let ingredient = statement.read::<String, _>("ingredient").unwrap();
+            // Rows come cheapest first within each ingredient
+            let cheapest = prices
+                .last()
+                .is_none_or(|last| last.ingredient.to_lowercase() != ingredient.to_lowercase());
+
+            prices.push(StorePrice {
+                ingredient,
+                store: statement.read::<String, _>("store").unwrap(),
+                average_price: Money::from_cents(
+                    statement.read::<f64, _>("average").unwrap().round() as i64,
+                ),
+                purchases: statement.read::<i64, _>("purchases").unwrap(),
+                cheapest,
+            });
+        }
+
+        prices
+    }
+
+    // Parses the search input into the active filter, see `query::parse` for the syntax
+    pub fn apply_query(&mut self) {
+        match query::parse(&self.query_input) {
+            Ok(filter) => {
+                self.filter = filter;
+                self.query_error = None;
+            }
+            Err(err) => self.query_error = Some(err),
+        }
+    }
+
+    // Returns whether a purchase with that rowid existed
+    pub fn update_expended(id: i64, new_date: String) -> bool {
+        let conn = db::open();
+        let transaction = db::Transaction::begin(&conn);
+        let Some(before) = audit::snapshot(&conn, id) else {
+            return false;
+        };
         let query = "UPDATE purchase SET expendedDate = ? WHERE rowid = ?";
 
         let mut statement = conn.prepare(query).unwrap();
 
         let _ = statement.bind((1, new_date.as_str()));
-        let _ = statement.bind((2, item_id.as_str()));
+        let _ = statement.bind((2, id));
 
         statement.next().unwrap();
+        audit::record(
+            &conn,
+            "update",
+            id,
+            Some(before),
+            audit::snapshot(&conn, id),
+        );
+        transaction.commit();
+        true
     }
 
-    pub fn get_monthly_meal_swipe_estimate() -> f64 {
+    // Returns whether a purchase with that rowid existed
+    pub fn delete_purchase(id: i64) -> bool {
+        let conn = db::open();
+        let transaction = db::Transaction::begin(&conn);
+        let Some(before) = audit::snapshot(&conn, id) else {
+            return false;
+        };
+        let mut statement = conn
+            .prepare("DELETE FROM purchase WHERE rowid = ?")
+            .unwrap();
+
+        let _ = statement.bind((1, id));
+
+        statement.next().unwrap();
+        audit::record(&conn, "delete", id, Some(before), None);
+        transaction.commit();
+        true
+    }
+
+    pub fn get_monthly_meal_swipe_estimate() -> Money {
         let today: NaiveDate = Local::now().date_naive();
         let first_of_month: NaiveDate = today.with_day(1).unwrap();
 
-        let days_passed_in_month = today - first_of_month;
-        (days_passed_in_month.num_days() as f64) * 15.12 * 2.0
+        App::get_period_meal_swipe_estimate(first_of_month, today).cost
+    }
+
+    // Saved through sales and coupons on purchases made this month, in home currency
+    pub fn get_monthly_savings() -> Money {
+        let today: NaiveDate = Local::now().date_naive();
+        App::get_period_savings(today.with_day(1).unwrap(), today)
+    }
+
+    pub fn get_period_savings(start: NaiveDate, end: NaiveDate) -> Money {
+        let conn = db::open();
+        let mut statement = conn
+            .prepare(format!(
+                "SELECT SUM({}) AS savings FROM purchase WHERE purchaseDate BETWEEN ? AND ?",
+                currency::home_sql(SAVINGS_SQL)
+            ))
+            .unwrap();
+        statement.bind((1, start.to_string().as_str())).unwrap();
+        statement.bind((2, end.to_string().as_str())).unwrap();
+        statement.next().unwrap();
+        Money::from_cents(statement.read::<i64, _>("savings").unwrap_or(0))
     }
 
     pub fn get_semesterly_meal_swipe_estimate() -> MealSwipeInfo {
+        let plan = MealPlan::load();
+        App::get_period_meal_swipe_estimate(plan.semester_start, plan.semester_end)
+    }
+
+    // Swipes estimated for the days of [start, end] that fall within the semester and have passed,
+    // never more than the semester's swipes
+    pub fn get_period_meal_swipe_estimate(start: NaiveDate, end: NaiveDate) -> MealSwipeInfo {
         let today: NaiveDate = Local::now().date_naive();
-        let first_date_semester: NaiveDate = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        let plan = MealPlan::load();
+        let first = start.max(plan.semester_start);
+        let last = end.min(plan.semester_end).min(today);
 
-        let days_passed_in_semester = today - first_date_semester;
-        let swipes_used = days_passed_in_semester.num_days() * 2;
-        let cost = swipes_used as f64 * 15.12;
+        let days = if last >= first {
+            (last - first).num_days() + 1
+        } else {
+            0
+        };
+        let swipes_used = (days * plan.swipes_per_day).min(plan.semester_swipes);
         MealSwipeInfo {
             swipes: swipes_used,
-            cost: cost,
+            cost: plan.swipe_price * swipes_used,
         }
     }
 
-    // Queries SQLite and returns Table Row Vector
-    pub fn get_ingredient_entries(&mut self) -> Vec<Vec<String>> {
-        let conn = sqlite::open("src/purchases.db").unwrap();
+    // Projects swipe usage to the end of the semester from the usage so far
+    pub fn get_swipe_forecast() -> SwipeForecast {
+        let plan = MealPlan::load();
+        let start = plan.semester_start;
+        let end = plan.semester_end;
+        let today = Local::now().date_naive().clamp(start, end);
+
+        // Today counts as passed so swipes recorded today are spread over it
+        let days_passed = (today - start).num_days() + 1;
+        let days_left = (end - today).num_days();
+        let swipes_used = App::count_swipes(start, today);
+        let swipes_remaining = (plan.semester_swipes - swipes_used).max(0);
+
+        let burn_rate = (swipes_used > 0).then(|| swipes_used as f64 / days_passed as f64);
+        let projected_used = swipes_used as f64 + burn_rate.unwrap_or(0.0) * days_left as f64;
+        let projected_leftover = plan.semester_swipes - projected_used.round() as i64;
+
+        // Counted from the semester start rather than today so the date holds still while the pace does
+        let run_out_date = match burn_rate {
+            Some(rate) if projected_leftover < 0 => {
+                let days_until_empty = (plan.semester_swipes as f64 / rate).ceil() as i64;
+                Some(start + Duration::days(days_until_empty - 1))
+            }
+            _ => None,
+        };
+
+        let weeks_left = days_left as f64 / 7.0;
+        let recommended_per_week = if weeks_left > 0.0 {
+            swipes_remaining as f64 / weeks_left
+        } else {
+            0.0
+        };
+
+        SwipeForecast {
+            swipes_used,
+            swipes_remaining,
+            burn_rate,
+            projected_leftover,
+            run_out_date,
+            recommended_per_week,
+        }
+    }
 
-        let query = format!("SELECT rowid, * FROM purchase WHERE {} ORDER BY {}", &self.search_param, &self.order_by);
-        let mut rows = Vec::<Vec<String>>::new();
+    pub fn submit_dining_transaction(&mut self) {
+        let conn = db::open();
+        let amount = Money::parse(&self.amount_input).unwrap();
+        let transaction_date = if self.transaction_date_input.is_empty() {
+            resolve_date_input("t")
+        } else {
+            resolve_date_input(&self.transaction_date_input)
+        };
 
+        let query =
+            "INSERT INTO dining_transaction (location, amount, transactionDate) VALUES (?, ?, ?)";
         let mut statement = conn.prepare(query).unwrap();
-        let mut i = 0;
 
+        statement.bind((1, self.location_input.as_str())).unwrap();
+        statement.bind((2, amount.cents())).unwrap();
+        statement.bind((3, transaction_date.as_str())).unwrap();
+
+        statement.next().unwrap();
+    }
+
+    pub fn set_dining_balance(&mut self) {
+        let balance = Money::parse(&self.dining_balance_input).unwrap();
+        db::set_setting("dining_starting_balance", &balance.cents().to_string());
+    }
+
+    pub fn get_dining_info() -> DiningInfo {
+        let conn = db::open();
+        let plan = MealPlan::load();
+        let start = plan.semester_start;
+        let end = plan.semester_end;
+        let today = Local::now().date_naive().clamp(start, end);
+
+        let starting_balance = db::get_setting("dining_starting_balance")
+            .and_then(|value| value.parse::<i64>().ok())
+            .map(Money::from_cents)
+            .unwrap_or_default();
+
+        let mut statement = conn
+            .prepare(
+                "SELECT SUM(amount) AS spent FROM dining_transaction WHERE transactionDate >= ?",
+            )
+            .unwrap();
+        statement.bind((1, start.to_string().as_str())).unwrap();
+        statement.next().unwrap();
+        let spent = Money::from_cents(statement.read::<i64, _>("spent").unwrap_or(0));
+
+        let remaining = starting_balance - spent;
+        let days_left = (end - today).num_days().max(1);
+
+        let days_passed = (today - start).num_days() + 1;
+        let run_out_date = if spent > Money::ZERO && starting_balance > Money::ZERO {
+            let days_until_empty = (starting_balance.cents() as f64 * days_passed as f64
+                / spent.cents() as f64)
+                .ceil() as i64;
+            Some(start + Duration::days(days_until_empty - 1)).filter(|date| *date <= end)
+        } else {
+            None
+        };
+
+        DiningInfo {
+            starting_balance,
+            remaining,
+            daily_allowance: remaining.max(Money::ZERO) / days_left,
+            run_out_date,
+        }
+    }
+
+    pub fn get_templates() -> Vec<Template> {
+        let conn = db::open();
+        let mut templates = Vec::<Template>::new();
+
+        let mut statement = conn
+            .prepare("SELECT * FROM template ORDER BY name")
+            .unwrap();
         while let State::Row = statement.next().unwrap() {
-            // Use Option<String> to handle possible NULL values
-            let rowid = statement.read::<String, _>("rowid").unwrap_or_else(|_| "Unknown".to_string());
-            let ingredient = statement.read::<String, _>("ingredient").unwrap_or_else(|_| "Unknown".to_string());
-            let price = format!("${:.2}",(statement.read::<i64, _>("price").unwrap_or_else(|_| 0) as f64) / 100.00);
-            let purchase_date = statement.read::<String, _>("purchaseDate").unwrap_or_else(|_| "Unknown".to_string());
-            let expended_date = statement.read::<String, _>("expendedDate").unwrap_or_else(|_| "Unknown".to_string());
-
-            rows.push(vec![
-                rowid,
-                ingredient,
-                price,
-                purchase_date,
-                expended_date,
-            ]);
+            let name = statement.read::<String, _>("name").unwrap();
+            let every_days = statement
+                .read::<Option<i64>, _>("everyDays")
+                .unwrap_or(None);
+            let last_applied = statement
+                .read::<Option<String>, _>("lastApplied")
+                .unwrap_or(None)
+                .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
 
-            i += 1;
+            let mut items = conn
+                .prepare("SELECT ingredient FROM template_item WHERE template = ?")
+                .unwrap();
+            items.bind((1, name.as_str())).unwrap();
+            let mut ingredients = Vec::<String>::new();
+            while let State::Row = items.next().unwrap() {
+                ingredients.push(items.read::<String, _>("ingredient").unwrap());
+            }
+
+            templates.push(Template {
+                name,
+                ingredients,
+                every_days,
+                last_applied,
+            });
         }
-        self.item_count = i;
 
-        rows
+        templates
     }
 
-    // Table Navigation Functions
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= (self.item_count - 1) as usize {
-                    0
-                } else {
-                    i + 1
-                }
+    pub fn get_due_templates() -> Vec<Template> {
+        App::get_templates()
+            .into_iter()
+            .filter(Template::is_due)
+            .collect()
+    }
+
+    pub fn submit_template(&mut self) {
+        let conn = db::open();
+        let every_days = self.template_every_input.parse::<i64>().ok();
+
+        let mut statement = conn
+            .prepare("INSERT OR REPLACE INTO template (name, everyDays, lastApplied) VALUES (?, ?, NULL)")
+            .unwrap();
+        statement
+            .bind((1, self.template_name_input.as_str()))
+            .unwrap();
+        statement.bind((2, every_days)).unwrap();
+        statement.next().unwrap();
+
+        let mut statement = conn
+            .prepare("DELETE FROM template_item WHERE template = ?")
+            .unwrap();
+        statement
+            .bind((1, self.template_name_input.as_str()))
+            .unwrap();
+        statement.next().unwrap();
+
+        for ingredient in self
+            .template_items_input
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let mut statement = conn
+                .prepare("INSERT INTO template_item (template, ingredient) VALUES (?, ?)")
+                .unwrap();
+            statement
+                .bind((1, self.template_name_input.as_str()))
+                .unwrap();
+            statement.bind((2, ingredient)).unwrap();
+            statement.next().unwrap();
+        }
+    }
+
+    pub fn delete_template(name: &str) {
+        let conn = db::open();
+
+        for query in [
+            "DELETE FROM template WHERE name = ?",
+            "DELETE FROM template_item WHERE template = ?",
+        ] {
+            let mut statement = conn.prepare(query).unwrap();
+            statement.bind((1, name)).unwrap();
+            statement.next().unwrap();
+        }
+    }
+
+    // Price and currency as stored on the latest purchase of an ingredient, before any discount or
+    // tax, so it can be entered again as a new purchase's price
+    pub fn get_last_price(ingredient: &str) -> Option<(Money, Option<String>)> {
+        let conn = db::open();
+        let mut statement = conn
+            .prepare("SELECT price, currency FROM purchase WHERE ingredient = ? ORDER BY purchaseDate DESC, rowid DESC LIMIT 1")
+            .unwrap();
+        statement.bind((1, ingredient)).unwrap();
+
+        match statement.next().unwrap() {
+            State::Row => Some((
+                Money::from_cents(statement.read::<i64, _>("price").unwrap_or(0)),
+                statement.read::<Option<String>, _>("currency").unwrap(),
+            )),
+            State::Done => None,
+        }
+    }
+
+    // Inserts one purchase per template item dated today at its last known price. Items never
+    // bought before have no price to use, so they are skipped and returned
+    pub fn apply_template(template: &Template) -> Vec<String> {
+        let today = resolve_date_input("t");
+        let mut skipped = Vec::<String>::new();
+
+        for ingredient in &template.ingredients {
+            let Some((price, currency)) = App::get_last_price(ingredient) else {
+                skipped.push(ingredient.clone());
+                continue;
+            };
+            let mut purchase = NewPurchase::new(ingredient, price, &today, "NULL");
+            purchase.currency = currency;
+            App::insert_purchase(&purchase);
+        }
+
+        App::mark_template_applied(&template.name);
+        skipped
+    }
+
+    pub fn template_status(name: &str, skipped: &[String]) -> String {
+        if skipped.is_empty() {
+            format!("Applied template {}", name)
+        } else {
+            format!(
+                "Applied template {}, add these by hand as they have no known price: {}",
+                name,
+                skipped.join(", ")
+            )
+        }
+    }
+
+    // Resets a recurring template's schedule without inserting anything
+    pub fn mark_template_applied(name: &str) {
+        let conn = db::open();
+        let mut statement = conn
+            .prepare("UPDATE template SET lastApplied = ? WHERE name = ?")
+            .unwrap();
+        statement
+            .bind((1, resolve_date_input("t").as_str()))
+            .unwrap();
+        statement.bind((2, name)).unwrap();
+        statement.next().unwrap();
+    }
+
+    // Suggests ingredients with nothing left in the pantry whose usual repurchase interval has passed
+    pub fn get_shopping_suggestions() -> Vec<ShoppingItem> {
+        let conn = db::open();
+        let today = Local::now().date_naive();
+        let mut suggestions = Vec::<ShoppingItem>::new();
+
+        let query = "SELECT ingredient, price, currency, purchaseDate, expendedDate FROM purchase ORDER BY ingredient, purchaseDate, rowid";
+        let mut statement = conn.prepare(query).unwrap();
+
+        // (ingredient, purchase dates, first expended date, in pantry, last price and currency)
+        let mut groups = Vec::<(
+            String,
+            Vec<NaiveDate>,
+            Option<NaiveDate>,
+            bool,
+            (Money, Option<String>),
+        )>::new();
+
+        while let State::Row = statement.next().unwrap() {
+            let ingredient = statement
+                .read::<String, _>("ingredient")
+                .unwrap_or_default();
+            let price = Money::from_cents(statement.read::<i64, _>("price").unwrap_or(0));
+            let currency = statement.read::<Option<String>, _>("currency").unwrap();
+            let purchase_date = statement
+                .read::<String, _>("purchaseDate")
+                .ok()
+                .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+            let expended_date = statement
+                .read::<String, _>("expendedDate")
+                .ok()
+                .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+
+            if groups
+                .last()
+                .map(|group| group.0 != ingredient)
+                .unwrap_or(true)
+            {
+                groups.push((ingredient, Vec::new(), None, false, (Money::ZERO, None)));
+            }
+            let group = groups.last_mut().unwrap();
+
+            if let Some(date) = purchase_date {
+                group.1.push(date);
+            }
+            if expended_date.is_none() {
+                group.3 = true;
+            } else if group.2.is_none() {
+                group.2 = expended_date;
+            }
+            group.4 = (price, currency);
+        }
+
+        for (ingredient, purchase_dates, first_expended, in_pantry, last_price) in groups {
+            let Some(&last_purchase) = purchase_dates.last() else {
+                continue;
+            };
+            if in_pantry {
+                continue;
+            }
+
+            // Average gap between purchases, or how long a single purchase lasted
+            let interval = if purchase_dates.len() > 1 {
+                (last_purchase - purchase_dates[0]).num_days() / (purchase_dates.len() as i64 - 1)
+            } else {
+                first_expended
+                    .map(|date| (date - last_purchase).num_days())
+                    .unwrap_or(0)
+            };
+
+            if (today - last_purchase).num_days() >= interval {
+                suggestions.push(ShoppingItem::new(&ingredient, Some(last_price)));
             }
-            None => 0,
+        }
+
+        suggestions
+    }
+
+    // Records everything on the shopping list with an estimate as bought today at that price.
+    // Items without one stay on the list, to be added by hand
+    pub fn convert_shopping_list(&mut self) {
+        let today = resolve_date_input("t");
+        let mut recorded = 0;
+
+        for item in std::mem::take(&mut self.shopping_list) {
+            let Some(price) = item.estimated_price else {
+                self.shopping_list.push(item);
+                continue;
+            };
+            let mut purchase = NewPurchase::new(&item.ingredient, price, &today, "NULL");
+            purchase.currency = item.currency;
+            App::insert_purchase(&purchase);
+            recorded += 1;
+        }
+
+        let unpriced: Vec<&str> = self
+            .shopping_list
+            .iter()
+            .map(|item| item.ingredient.as_str())
+            .collect();
+        self.status_message = if unpriced.is_empty() {
+            format!("Recorded {} purchases", recorded)
+        } else {
+            format!(
+                "Recorded {} purchases, kept on the list as they have no known price: {}",
+                recorded,
+                unpriced.join(", ")
+            )
         };
-        self.state.select(Some(i));
-        self.scroll_state = self.scroll_state.position(i);
     }
-    pub fn prev(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.item_count as usize - 1
+
+    // Moves to another profile's database, dropping state loaded from the previous one
+    pub fn switch_profile(&mut self, name: &str) {
+        db::set_profile(name);
+
+        self.state.select(Some(0));
+        self.row_offset = 0;
+        self.refresh_rows();
+        self.shopping_list.clear();
+        self.templates.clear();
+        self.columns = ColumnSettings::load();
+        self.due_templates = App::get_due_templates();
+        self.status_message = format!("Switched to profile {}", name);
+        self.current_screen = if self.due_templates.is_empty() {
+            CurrentScreen::Main
+        } else {
+            CurrentScreen::RecurringConfirm
+        };
+    }
+
+    pub fn record_swipes(swipes: i64, swipe_date: &str) {
+        let conn = db::open();
+        let mut statement = conn
+            .prepare("INSERT INTO swipe (swipeDate, swipes) VALUES (?, ?)")
+            .unwrap();
+        statement.bind((1, swipe_date)).unwrap();
+        statement.bind((2, swipes)).unwrap();
+
+        statement.next().unwrap();
+    }
+
+    // Swipes recorded for the days of [start, end]
+    pub fn count_swipes(start: NaiveDate, end: NaiveDate) -> i64 {
+        let conn = db::open();
+        let mut statement = conn
+            .prepare("SELECT SUM(swipes) AS swipes FROM swipe WHERE swipeDate BETWEEN ? AND ?")
+            .unwrap();
+        statement.bind((1, start.to_string().as_str())).unwrap();
+        statement.bind((2, end.to_string().as_str())).unwrap();
+        statement.next().unwrap();
+        statement.read::<i64, _>("swipes").unwrap_or(0)
+    }
+
+    pub fn record_meal(servings: i64) {
+        let conn = db::open();
+        let query = "INSERT INTO meal (mealDate, servings) VALUES (?, ?)";
+        let today = Local::now();
+
+        let mut statement = conn.prepare(query).unwrap();
+        statement
+            .bind((1, format!("{}", today.format("%Y-%m-%d")).as_str()))
+            .unwrap();
+        statement.bind((2, servings)).unwrap();
+
+        statement.next().unwrap();
+    }
+
+    // Compares the cost of a home-cooked meal this semester against a meal swipe
+    pub fn get_break_even_analysis() -> BreakEvenInfo {
+        let conn = db::open();
+        let plan = MealPlan::load();
+        let start = plan.semester_start;
+        let end = plan.semester_end;
+
+        let mut statement = conn
+            .prepare(format!(
+                "SELECT SUM({}) AS spend FROM purchase WHERE purchaseDate BETWEEN ? AND ?",
+                home_price_sql()
+            ))
+            .unwrap();
+        statement.bind((1, start.to_string().as_str())).unwrap();
+        statement.bind((2, end.to_string().as_str())).unwrap();
+        statement.next().unwrap();
+        let grocery_spend = Money::from_cents(statement.read::<i64, _>("spend").unwrap_or(0));
+
+        let mut statement = conn
+            .prepare("SELECT SUM(servings) AS meals FROM meal WHERE mealDate BETWEEN ? AND ?")
+            .unwrap();
+        statement.bind((1, start.to_string().as_str())).unwrap();
+        statement.bind((2, end.to_string().as_str())).unwrap();
+        statement.next().unwrap();
+        let home_meals = statement.read::<i64, _>("meals").unwrap_or(0);
+
+        let cost_per_home_meal = (home_meals > 0).then(|| grocery_spend / home_meals);
+        let savings_per_meal = cost_per_home_meal.map(|cost| plan.swipe_price - cost);
+        let semester_weeks = (end - start).num_days() / 7;
+
+        // Unused swipes are paid for but never eaten, and each swipe swapped for cooking saves the difference
+        let forecast = App::get_swipe_forecast();
+        let (swipes_to_drop, drop_savings) = match (forecast.burn_rate, savings_per_meal) {
+            (Some(_), Some(savings)) => {
+                let unused = forecast.projected_leftover.max(0);
+                let swapped = if savings > Money::ZERO {
+                    semester_weeks
                 } else {
-                    i - 1
-                }
+                    0
+                };
+                (
+                    Some(unused + swapped),
+                    plan.swipe_price * unused + savings * swapped,
+                )
             }
-            None => 0,
+            _ => (None, Money::ZERO),
         };
-        self.state.select(Some(i));
-        self.scroll_state = self.scroll_state.position(i);
+
+        BreakEvenInfo {
+            grocery_spend,
+            home_meals,
+            cost_per_home_meal,
+            savings_per_meal,
+            swipes_to_drop,
+            swipe_price: plan.swipe_price,
+            drop_savings,
+            weekly_swap_savings: savings_per_meal.map(|savings| savings * semester_weeks),
+        }
+    }
+
+    pub fn apply_view(&mut self, view: &View) {
+        self.query_input = view.search.clone();
+        self.sort = view.sort.clone();
+        self.apply_query();
+        self.state.select(Some(0));
+        self.status_message = format!("View: {}", view.name);
+    }
+
+    // Saves the current search and sort under the name typed into the view popup
+    pub fn save_view(&mut self) {
+        let view = View {
+            name: self.view_input.trim().to_string(),
+            search: self.query_input.clone(),
+            sort: self.sort.clone(),
+        };
+        view::save(&view);
+
+        self.status_message = format!("Saved view {}", view.name);
+        self.view_input.clear();
+        self.views = view::all();
+    }
+
+    // Statement selecting the rows matching the active search and sort, best fuzzy matches first
+    // while searching, optionally only the `limit` rows after `offset`
+    pub fn prepare_entries<'c>(
+        &self,
+        conn: &'c Connection,
+        window: Option<(usize, usize)>,
+    ) -> sqlite::Result<Statement<'c>> {
+        let mut params = self.filter.params.clone();
+        let mut order_by = self.sort.order_by();
+
+        if !self.filter.terms.is_empty() {
+            let scores = vec!["coalesce(fuzzy(?, ingredient, store), 0)"; self.filter.terms.len()];
+            order_by = format!("{} DESC, {}", scores.join(" + "), order_by);
+            params.extend(
+                self.filter
+                    .terms
+                    .iter()
+                    .map(|term| Value::String(term.clone())),
+            );
+        }
+
+        let mut query = format!(
+            "SELECT rowid, *, {} AS homePrice FROM purchase WHERE {} ORDER BY {}",
+            home_price_sql(),
+            &self.filter.clause,
+            order_by
+        );
+        if let Some((offset, limit)) = window {
+            query.push_str(" LIMIT ? OFFSET ?");
+            params.push(Value::Integer(limit as i64));
+            params.push(Value::Integer(offset as i64));
+        }
+
+        let mut statement = conn.prepare(query)?;
+        statement.bind(&params[..])?;
+
+        Ok(statement)
+    }
+
+    // Every row matching the active search and sort
+    pub fn get_ingredient_entries(&self) -> Vec<Transaction> {
+        self.read_entries(None)
+    }
+
+    fn read_entries(&self, window: Option<(usize, usize)>) -> Vec<Transaction> {
+        let conn = db::open();
+
+        let mut rows = Vec::<Transaction>::new();
+
+        let mut statement = self.prepare_entries(&conn, window).unwrap();
+
+        while let State::Row = statement.next().unwrap() {
+            rows.push(Transaction::read(&statement).unwrap());
+        }
+
+        rows
+    }
+
+    fn count_entries(&self) -> usize {
+        let conn = db::open();
+        let query = format!(
+            "SELECT count(*) AS count FROM purchase WHERE {}",
+            &self.filter.clause
+        );
+        let mut statement = conn.prepare(query).unwrap();
+        statement.bind(&self.filter.params[..]).unwrap();
+
+        statement.next().unwrap();
+        statement.read::<i64, _>("count").unwrap() as usize
+    }
+
+    // Reloads the cached rows on the next draw, call after changing purchases
+    pub fn refresh_rows(&mut self) {
+        self.rows_key = None;
+    }
+
+    // Loads the rows around the selection if the cache is stale or the search or sort changed
+    pub fn load_rows(&mut self) {
+        let key = format!(
+            "{} {:?} {}",
+            self.filter.clause,
+            self.filter.params,
+            self.sort.order_by()
+        );
+        if self.rows_key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let selected = self.selected_index();
+        self.rows_key = Some(key);
+        self.item_count = self.count_entries();
+        self.scroll_state = self.scroll_state.content_length(self.item_count);
+        self.fetch_window(selected);
+        self.select_index(selected);
+    }
+
+    // Replaces the cached rows with the window around `index`, keeping the table scrolled where it was
+    fn fetch_window(&mut self, index: usize) {
+        let top = self.row_offset + self.state.offset();
+        self.row_offset = index.saturating_sub(ROW_WINDOW / 2);
+        self.row_data = self.read_entries(Some((self.row_offset, ROW_WINDOW)));
+        *self.state.offset_mut() = top.saturating_sub(self.row_offset);
+    }
+
+    // Position of the selected row among all matching rows, not just the cached ones
+    pub fn selected_index(&self) -> usize {
+        self.row_offset + self.state.selected().unwrap_or(0)
+    }
+
+    // Selects a row by its position among all matching rows, fetching its window if it isn't cached
+    pub fn select_index(&mut self, index: usize) {
+        let index = index.min(self.item_count.saturating_sub(1));
+        if index < self.row_offset || index >= self.row_offset + self.row_data.len() {
+            self.fetch_window(index);
+        }
+
+        self.state.select(Some(index - self.row_offset));
+        self.scroll_state = self.scroll_state.position(index);
+    }
+
+    // Currently selected row, None when nothing matches
+    pub fn selected_row(&self) -> Option<&Transaction> {
+        self.state.selected().and_then(|i| self.row_data.get(i))
+    }
+
+    // Table Navigation Functions
+    pub fn next(&mut self) {
+        let i = self.selected_index();
+        self.select_index(if i + 1 >= self.item_count { 0 } else { i + 1 });
+    }
+    pub fn prev(&mut self) {
+        let i = self.selected_index();
+        self.select_index(if i == 0 {
+            self.item_count.saturating_sub(1)
+        } else {
+            i - 1
+        });
+    }
+
+    pub fn page_down(&mut self) {
+        self.select_index(self.selected_index() + self.page_height.max(1));
+    }
+
+    pub fn page_up(&mut self) {
+        self.select_index(
+            self.selected_index()
+                .saturating_sub(self.page_height.max(1)),
+        );
     }
 }
@@ -0,0 +1,133 @@
+use chrono::Local;
+use serde_json::{Map, Value as Json};
+use sqlite::{Connection, State, Value};
+
+use crate::db;
+
+pub struct AuditEntry {
+    /// Table of the changed row when it isn't a purchase
+    pub table: Option<String>,
+    /// Rowid of the changed row, a purchase unless `table` says otherwise
+    pub purchase_id: i64,
+    pub operation: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub changed_at: String,
+}
+
+// Current values of a purchase row as a JSON object, None if the row doesn't exist
+pub fn snapshot(conn: &Connection, purchase_id: i64) -> Option<String> {
+    snapshot_row(conn, "purchase", purchase_id)
+}
+
+// Current values of any row as a JSON object, `table` is always one of ours and never user input
+pub fn snapshot_row(conn: &Connection, table: &str, rowid: i64) -> Option<String> {
+    let mut statement = conn
+        .prepare(format!("SELECT * FROM {} WHERE rowid = ?", table))
+        .unwrap();
+    statement.bind((1, rowid)).unwrap();
+
+    if let State::Done = statement.next().unwrap() {
+        return None;
+    }
+
+    let mut row = Map::new();
+    for (i, column) in statement.column_names().to_vec().iter().enumerate() {
+        let value = match statement.read::<Value, _>(i).unwrap_or(Value::Null) {
+            Value::Integer(value) => Json::from(value),
+            Value::Float(value) => Json::from(value),
+            Value::String(value) => Json::from(value),
+            Value::Binary(_) | Value::Null => Json::Null,
+        };
+        row.insert(column.clone(), value);
+    }
+
+    Some(Json::Object(row).to_string())
+}
+
+// Appends an entry for a purchase to the audit log, the log is never updated or deleted from
+pub fn record(
+    conn: &Connection,
+    operation: &str,
+    purchase_id: i64,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    write_entry(conn, None, operation, purchase_id, before, after);
+}
+
+// Appends an entry for a row of another table, such as a setting or dining transaction
+pub fn record_row(
+    conn: &Connection,
+    table: &str,
+    operation: &str,
+    rowid: i64,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    write_entry(conn, Some(table), operation, rowid, before, after);
+}
+
+fn write_entry(
+    conn: &Connection,
+    table: Option<&str>,
+    operation: &str,
+    rowid: i64,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    let query = "INSERT INTO audit (operation, purchaseId, beforeValue, afterValue, changedAt, tableName) VALUES (?, ?, ?, ?, ?, ?)";
+    let mut statement = conn.prepare(query).unwrap();
+
+    statement.bind((1, operation)).unwrap();
+    statement.bind((2, rowid)).unwrap();
+    statement.bind((3, before.as_deref())).unwrap();
+    statement.bind((4, after.as_deref())).unwrap();
+    statement
+        .bind((
+            5,
+            Local::now()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .as_str(),
+        ))
+        .unwrap();
+    statement.bind((6, table)).unwrap();
+
+    statement.next().unwrap();
+}
+
+// Audit entries for one purchase, or for every purchase when `purchase_id` is None, newest first
+pub fn entries(purchase_id: Option<i64>) -> Vec<AuditEntry> {
+    let conn = db::open();
+    let query = match purchase_id {
+        Some(_) => {
+            "SELECT * FROM audit WHERE purchaseId = ? AND tableName IS NULL ORDER BY rowid DESC"
+        }
+        None => "SELECT * FROM audit ORDER BY rowid DESC",
+    };
+    let mut statement = conn.prepare(query).unwrap();
+    if let Some(purchase_id) = purchase_id {
+        statement.bind((1, purchase_id)).unwrap();
+    }
+
+    let mut entries = Vec::<AuditEntry>::new();
+    while let State::Row = statement.next().unwrap() {
+        entries.push(AuditEntry {
+            table: statement
+                .read::<Option<String>, _>("tableName")
+                .unwrap_or(None),
+            purchase_id: statement.read::<i64, _>("purchaseId").unwrap_or(0),
+            operation: statement.read::<String, _>("operation").unwrap_or_default(),
+            before: statement
+                .read::<Option<String>, _>("beforeValue")
+                .unwrap_or(None),
+            after: statement
+                .read::<Option<String>, _>("afterValue")
+                .unwrap_or(None),
+            changed_at: statement.read::<String, _>("changedAt").unwrap_or_default(),
+        });
+    }
+
+    entries
+}
@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use sqlite::{ffi, Connection};
+
+use crate::db;
+
+/// Backups kept when no `backup_retention` setting has been saved
+const DEFAULT_RETENTION: usize = 10;
+
+// Each profile keeps its own backups, the default profile's stay directly in the backups folder
+pub fn backup_dir() -> PathBuf {
+    let backups = Path::new(db::DB_PATH)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("backups");
+    match db::profile().as_str() {
+        db::DEFAULT_PROFILE => backups,
+        name => backups.join(name),
+    }
+}
+
+pub fn retention() -> usize {
+    db::get_setting("backup_retention")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_RETENTION)
+}
+
+pub fn set_retention(retention: usize) {
+    db::set_setting("backup_retention", &retention.to_string());
+}
+
+// Copies every page of `source` into `destination` with SQLite's online backup API
+fn copy_database(source: &Connection, destination: &Connection) -> Result<(), Box<dyn Error>> {
+    // SAFETY: both handles come from open `Connection`s borrowed for the whole call, so they stay
+    // valid until after `sqlite3_backup_finish`, and the schema names are NUL terminated literals.
+    // The backup handle is checked for null before use and finished exactly once, and neither
+    // connection is used by anything else while the backup runs.
+    unsafe {
+        let backup = ffi::sqlite3_backup_init(
+            destination.as_raw(),
+            c"main".as_ptr(),
+            source.as_raw(),
+            c"main".as_ptr(),
+        );
+        if backup.is_null() {
+            return Err("could not start backup".into());
+        }
+
+        let result = ffi::sqlite3_backup_step(backup, -1);
+        ffi::sqlite3_backup_finish(backup);
+
+        if result != ffi::SQLITE_DONE {
+            return Err(format!("backup failed with SQLite error {}", result).into());
+        }
+    }
+
+    Ok(())
+}
+
+// Names of the saved backups, oldest first
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(backup_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.ends_with(".db"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Timestamped names sort chronologically
+    names.sort();
+    names
+}
+
+// Takes a timestamped backup of the live database, returning its file name
+pub fn create() -> Result<String, Box<dyn Error>> {
+    fs::create_dir_all(backup_dir())?;
+
+    let name = format!("purchases-{}.db", Local::now().format("%Y%m%d-%H%M%S%.3f"));
+    let destination = sqlite::open(backup_dir().join(&name))?;
+    copy_database(&db::open(), &destination)?;
+
+    Ok(name)
+}
+
+// Deletes the oldest backups beyond the configured retention, a retention of 0 keeps everything
+pub fn rotate() -> Result<(), Box<dyn Error>> {
+    let retention = retention();
+    if retention == 0 {
+        return Ok(());
+    }
+
+    let names = list();
+    let excess = names.len().saturating_sub(retention);
+
+    for name in &names[..excess] {
+        fs::remove_file(backup_dir().join(name))?;
+    }
+
+    Ok(())
+}
+
+// Backup taken on startup and before CLI commands that write, skipped when retention is set to 0
+pub fn create_and_rotate() -> Result<(), Box<dyn Error>> {
+    if retention() > 0 {
+        create()?;
+    }
+    rotate()
+}
+
+// Replaces the live database with a backup, saving the current state first
+pub fn restore(name: &str) -> Result<String, Box<dyn Error>> {
+    let path = backup_dir().join(name);
+    if !list().iter().any(|backup| backup == name) {
+        return Err(format!("no backup named \"{}\"", name).into());
+    }
+
+    let safety_backup = create()?;
+    copy_database(&sqlite::open(path)?, &db::open())?;
+
+    Ok(safety_backup)
+}
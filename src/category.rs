@@ -0,0 +1,297 @@
+use std::ffi::{c_char, c_int, CStr};
+
+use sqlite::{ffi, Connection};
+
+/// Keywords checked against the words of the lowercase ingredient name, in order, so more specific
+/// categories (e.g. "chicken broth" as pantry) win over broader ones (chicken as meat). A keyword
+/// matches whole words, its last word also in the plural.
+const CATEGORY_KEYWORDS: [(&str, &[&str]); 10] = [
+    (
+        "prepared",
+        &[
+            "burrito",
+            "pad thai",
+            "smoothie",
+            "milkshake",
+            "pizza",
+            "sandwich",
+            "takeout",
+        ],
+    ),
+    ("frozen", &["frozen"]),
+    (
+        "beverages",
+        &[
+            "dr pepper",
+            "root beer",
+            "soda",
+            "juice",
+            "coffee",
+            "tea",
+            "water",
+            "cola",
+        ],
+    ),
+    (
+        "snacks",
+        &[
+            "chips",
+            "pretzel",
+            "pocky",
+            "hummus",
+            "cookie",
+            "klondike",
+            "ice cream",
+            "candy",
+            "crackers",
+        ],
+    ),
+    (
+        "pantry",
+        &[
+            "broth",
+            "coconut milk",
+            "mac & cheese",
+            "paste",
+            "sauce",
+            "rice",
+            "flour",
+            "sugar",
+            "oil",
+            "vinegar",
+            "lentil",
+            "cereal",
+            "tomatoes",
+            "mustard",
+            "pasta",
+            "beans",
+            "oats",
+        ],
+    ),
+    (
+        "spices",
+        &[
+            "seasoning",
+            "powder",
+            "crushed red pepper",
+            "cayenne",
+            "paprika",
+            "oregano",
+            "thyme",
+            "tyhme",
+            "tumeric",
+            "turmeric",
+            "cinnamon",
+            "cumin",
+            "chipotle",
+            "salt",
+        ],
+    ),
+    (
+        "produce",
+        &[
+            "onion",
+            "garlic",
+            "ginger",
+            "banana",
+            "pepper",
+            "lime",
+            "lemon",
+            "potato",
+            "strawberry",
+            "strawberries",
+            "squash",
+            "cilantro",
+            "cauliflower",
+            "broccoli",
+            "lettuce",
+            "apple",
+            "carrot",
+            "spinach",
+        ],
+    ),
+    (
+        "bakery",
+        &["roll", "bread", "muffin", "tortilla", "bagel", "bun"],
+    ),
+    (
+        "meat",
+        &[
+            "chicken",
+            "turkey",
+            "beef",
+            "ham",
+            "hamburger",
+            "bacon",
+            "hot dog",
+            "burger",
+            "pork",
+            "sausage",
+            "fish",
+        ],
+    ),
+    (
+        "dairy",
+        &["milk", "cheese", "butter", "cream", "yogurt", "egg"],
+    ),
+];
+
+/// Every category `categorize` can return
+pub const CATEGORIES: [&str; 11] = [
+    "prepared",
+    "frozen",
+    "beverages",
+    "snacks",
+    "pantry",
+    "spices",
+    "produce",
+    "bakery",
+    "meat",
+    "dairy",
+    "other",
+];
+
+// Lowercase words of a name, splitting on anything that isn't a letter or digit
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Whether `keyword` appears as consecutive whole words of `name`, its last word optionally plural
+fn contains_words(name: &[String], keyword: &str) -> bool {
+    let keyword = words(keyword);
+    let Some((last, leading)) = keyword.split_last() else {
+        return false;
+    };
+
+    name.windows(keyword.len()).any(|window| {
+        let (found_last, found_leading) = window.split_last().unwrap();
+        found_leading == leading
+            && found_last
+                .strip_prefix(last.as_str())
+                .is_some_and(|suffix| ["", "s", "es"].contains(&suffix))
+    })
+}
+
+// Guesses a category for an ingredient from its name
+pub fn categorize(ingredient: &str) -> &'static str {
+    let name = words(ingredient);
+
+    CATEGORY_KEYWORDS
+        .iter()
+        .find(|(_, keywords)| {
+            keywords
+                .iter()
+                .any(|keyword| contains_words(&name, keyword))
+        })
+        .map(|(category, _)| *category)
+        .unwrap_or("other")
+}
+
+// The same guess as `categorize` as an SQL expression over `column`, so searches can filter by
+// category. It calls the `category` function `register` adds, so both sides match words alike
+pub fn sql_expression(column: &str) -> String {
+    format!("category({})", column)
+}
+
+// Registers `category(ingredient)` on the connection
+pub fn register(conn: &Connection) -> Result<(), String> {
+    // SAFETY: the connection handle is valid for the call, the name is a NUL terminated literal and
+    // `category_sql` matches the signature SQLite expects for a scalar function taking one argument
+    let result = unsafe {
+        ffi::sqlite3_create_function_v2(
+            conn.as_raw(),
+            c"category".as_ptr(),
+            1,
+            ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+            std::ptr::null_mut(),
+            Some(category_sql),
+            None,
+            None,
+            None,
+        )
+    };
+    if result != ffi::SQLITE_OK {
+        return Err(format!(
+            "could not register the category function, SQLite error {}",
+            result
+        ));
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn category_sql(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: SQLite passes `argc` valid values in `argv`, one as registered. The text stays valid
+    // until the value is converted again or freed, neither of which happens while it is borrowed here
+    let ingredient = if argc == 1 {
+        let text = ffi::sqlite3_value_text(*argv);
+        if text.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(text as *const c_char).to_str().unwrap_or("")
+        }
+    } else {
+        ""
+    };
+
+    // SAFETY: categories are static strings, so SQLite can keep the pointer without copying it,
+    // which a NULL destructor (SQLITE_STATIC) tells it
+    let category = categorize(ingredient);
+    ffi::sqlite3_result_text(
+        context,
+        category.as_ptr() as *const c_char,
+        category.len() as c_int,
+        None,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_whole_words() {
+        assert_eq!(categorize("Steak"), "other");
+        assert_eq!(categorize("Eggplant"), "other");
+        assert_eq!(categorize("Watermelon"), "other");
+        assert_eq!(categorize("Shampoo"), "other");
+        assert_eq!(categorize("Green Tea"), "beverages");
+        assert_eq!(categorize("Sliced Ham"), "meat");
+    }
+
+    #[test]
+    fn matches_plurals_and_phrases() {
+        assert_eq!(categorize("Eggs (36)"), "dairy");
+        assert_eq!(categorize("Potatoes"), "produce");
+        assert_eq!(categorize("Strawberries"), "produce");
+        assert_eq!(categorize("Mac & Cheese"), "pantry");
+        assert_eq!(categorize("Chicken Broth"), "pantry");
+        assert_eq!(categorize("Chicken Thighs (3 LB)"), "meat");
+        assert_eq!(categorize("Ben & Jerry's Ice Cream"), "snacks");
+    }
+
+    #[test]
+    fn sql_function_matches_categorize() {
+        let conn = sqlite::open(":memory:").unwrap();
+        register(&conn).unwrap();
+        for name in [
+            "Steak",
+            "ÉCLAIR Eggs",
+            "Dr Pepper",
+            "Watermelon",
+            "Chicken Broth",
+        ] {
+            let mut statement = conn.prepare("SELECT category(?)").unwrap();
+            statement.bind((1, name)).unwrap();
+            statement.next().unwrap();
+            assert_eq!(statement.read::<String, _>(0).unwrap(), categorize(name));
+        }
+    }
+}
@@ -0,0 +1,690 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+
+use chrono::{Local, NaiveDate};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::app::{
+    parse_names, resolve_date_input, App, CurrentScreen, MealPlan, NewPurchase, Transaction,
+};
+use crate::backup;
+use crate::column::Column;
+use crate::currency;
+use crate::db;
+use crate::export;
+use crate::import::{self, ColumnMapping};
+use crate::money::{self, Money};
+use crate::pricing;
+use crate::report::{self, Period, ReportFormat};
+use crate::sort::{Direction, Sort};
+use crate::view;
+
+#[derive(Parser)]
+#[command(about = "Track grocery purchases against a meal plan")]
+pub struct Cli {
+    /// Ledger to use, each profile has its own database and meal plan
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add a purchase
+    Add {
+        #[arg(short, long)]
+        ingredient: String,
+        /// Price, e.g. 4.99, $4.99 or 4,99
+        #[arg(short, long)]
+        price: String,
+        /// Regular shelf price when bought on sale
+        #[arg(long)]
+        original_price: Option<String>,
+        /// Coupon or discount taken off the price
+        #[arg(long)]
+        discount: Option<String>,
+        /// Tax in percent charged on the price less the discount, e.g. 8.25
+        #[arg(long)]
+        tax_rate: Option<String>,
+        /// Purchase date as YYYY-MM-DD, "t" for today or "y" for yesterday
+        #[arg(short, long, default_value = "t")]
+        date: String,
+        /// Expended date, leave out for items still in the pantry
+        #[arg(short, long)]
+        expended: Option<String>,
+        /// Store the purchase was made at
+        #[arg(long)]
+        store: Option<String>,
+        /// Currency code the price is in, e.g. EUR, defaults to the home currency
+        #[arg(long)]
+        currency: Option<String>,
+        /// Roommate who paid
+        #[arg(long)]
+        paid_by: Option<String>,
+        /// Comma separated roommates sharing the cost
+        #[arg(long)]
+        split: Option<String>,
+    },
+    /// List transactions
+    List {
+        /// Search in the Q screen syntax, e.g. "milk price>3 store:aldi"
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long, value_enum, default_value_t = SortOrder::Smart)]
+        sort: SortOrder,
+        /// Saved view to list, replaces --filter and --sort
+        #[arg(long, conflicts_with_all = ["filter", "sort"])]
+        view: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Mark a purchase as expended
+    Expend {
+        id: i64,
+        /// Expended date, defaults to today
+        #[arg(default_value = "t")]
+        date: String,
+    },
+    /// Delete a purchase
+    Delete { id: i64 },
+    /// Record meal swipes used
+    Swipe {
+        #[arg(short, long, default_value_t = 1)]
+        count: i64,
+        /// Date the swipes were used as YYYY-MM-DD, "t" for today or "y" for yesterday
+        #[arg(short, long, default_value = "t")]
+        date: String,
+    },
+    /// Print the meal plan statistics
+    Stats,
+    /// Show the meal plan, or change any of its terms
+    Plan {
+        /// Price of one meal swipe
+        #[arg(long)]
+        swipe_price: Option<String>,
+        #[arg(long)]
+        swipes_per_day: Option<i64>,
+        /// Swipes included for the whole semester
+        #[arg(long)]
+        semester_swipes: Option<i64>,
+        /// First day of the semester as YYYY-MM-DD
+        #[arg(long)]
+        start: Option<String>,
+        /// Last day of the semester as YYYY-MM-DD
+        #[arg(long)]
+        end: Option<String>,
+    },
+    /// Show how amounts are written, or change the currency symbol and separators
+    Currency {
+        /// Written before amounts, 1 to 3 characters such as $, € or CHF
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Character before the cents, e.g. "," for 4,99
+        #[arg(long)]
+        decimal: Option<char>,
+        /// Character grouping thousands, "none" to leave them ungrouped
+        #[arg(long)]
+        thousands: Option<String>,
+    },
+    /// List the profiles that have a database
+    Profiles,
+    /// Compare average prices of ingredients bought at more than one store
+    Stores,
+    /// Manage database backups
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Manage exchange rates for purchases made in other currencies
+    Rates {
+        #[command(subcommand)]
+        command: RatesCommand,
+    },
+    /// Write a weekly or monthly spending report
+    Report {
+        #[arg(long, value_enum, default_value_t = Period::Month)]
+        period: Period,
+        /// Month to report on as YYYY-MM, defaults to the current month
+        #[arg(long)]
+        month: Option<String>,
+        /// Any day in the week to report on as YYYY-MM-DD, defaults to the current week
+        #[arg(long)]
+        week: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+        /// File to write, defaults to stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Export transactions to CSV, JSON or a Ledger/hledger journal
+    Export {
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// File to write, defaults to stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Account the ledger purchases are paid from
+        #[arg(long, default_value = "assets:cash")]
+        account: String,
+        /// Search in the Q screen syntax, e.g. "milk price>3 store:aldi"
+        #[arg(short, long)]
+        search: Option<String>,
+        #[arg(long, value_enum, default_value_t = SortOrder::Smart)]
+        sort: SortOrder,
+    },
+    /// Import transactions from CSV, previewing them in the TUI before saving
+    Import {
+        file: String,
+        /// Column holding the ingredient, by header name or 1-based index
+        #[arg(long, default_value = "ingredient")]
+        ingredient: String,
+        /// Column holding the price
+        #[arg(long, default_value = "price")]
+        price: String,
+        /// Column holding the purchase date
+        #[arg(long, default_value = "purchase_date")]
+        purchase_date: String,
+        /// Column holding the expended date, if the file has one
+        #[arg(long)]
+        expended_date: Option<String>,
+        /// Save without the preview screen
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+impl Command {
+    // Whether the command can write to the database, so a backup is taken before it runs. Restoring
+    // saves its own backup first
+    pub fn mutates(&self) -> bool {
+        match self {
+            Command::Add { .. }
+            | Command::Expend { .. }
+            | Command::Delete { .. }
+            | Command::Swipe { .. }
+            | Command::Plan { .. }
+            | Command::Currency { .. }
+            | Command::Import { .. } => true,
+            Command::Rates { command } => !matches!(command, RatesCommand::List),
+            Command::List { .. }
+            | Command::Stats
+            | Command::Profiles
+            | Command::Stores
+            | Command::Backup { .. }
+            | Command::Report { .. }
+            | Command::Export { .. } => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Take a backup now
+    Create,
+    /// List saved backups, oldest first
+    List,
+    /// Replace the database with a backup (the current state is backed up first)
+    Restore { name: String },
+    /// Set how many backups to keep, 0 disables automatic backups and pruning
+    Retention { count: usize },
+}
+
+#[derive(Subcommand)]
+pub enum RatesCommand {
+    /// List exchange rates and currencies still missing one
+    List,
+    /// Set what one unit of a currency is worth in the home currency
+    Set { currency: String, rate: f64 },
+    /// Remove a currency's exchange rate
+    Delete { currency: String },
+    /// Load rates from a CSV with currency and rate columns
+    Import { path: String },
+    /// Change the currency statistics are shown in
+    Home { currency: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ListFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ledger,
+}
+
+/// Sort orders available from the main screen keybindings
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortOrder {
+    Smart,
+    Price,
+    PriceAsc,
+    Date,
+    DateAsc,
+}
+
+impl SortOrder {
+    pub fn sort(&self) -> Sort {
+        match self {
+            SortOrder::Smart => Sort::default(),
+            SortOrder::Price => Sort::by(Column::Price, Direction::Desc),
+            SortOrder::PriceAsc => Sort::by(Column::Price, Direction::Asc),
+            SortOrder::Date => Sort::by(Column::PurchaseDate, Direction::Desc),
+            SortOrder::DateAsc => Sort::by(Column::PurchaseDate, Direction::Asc),
+        }
+    }
+}
+
+// Applies the same search and sort the Query screen and sort keys would
+// Resolves "t" and "y" like the TUI does, then checks the result is a real YYYY-MM-DD date
+fn resolve_date(input: &str) -> Result<String, String> {
+    let date = resolve_date_input(input.trim());
+    NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .map_err(|_| {
+            format!(
+                "invalid date \"{}\", expected YYYY-MM-DD, t for today or y for yesterday",
+                input
+            )
+        })
+}
+
+fn apply_search(
+    app: &mut App,
+    search: Option<String>,
+    sort: SortOrder,
+) -> Result<(), Box<dyn Error>> {
+    app.sort = sort.sort();
+    if let Some(search) = search {
+        app.query_input = search;
+        app.apply_query();
+    }
+
+    match app.query_error.take() {
+        Some(err) => Err(format!("invalid search: {}", err).into()),
+        None => Ok(()),
+    }
+}
+
+fn print_table(rows: &[Transaction]) {
+    println!(
+        "{:<6} {:<32} {:>12} {:<16} {:<13} {:<13}",
+        "ID", "Ingredient", "Net Price", "Store", "Purchased", "Expended"
+    );
+    for row in rows {
+        println!(
+            "{:<6} {:<32} {:>12} {:<16} {:<13} {:<13}",
+            row.id,
+            row.ingredient,
+            row.price_text(),
+            row.store.as_deref().unwrap_or_default(),
+            row.purchase_text(),
+            row.expended_text(),
+        );
+    }
+}
+
+fn print_stats() {
+    let semester = App::get_semesterly_meal_swipe_estimate();
+    let forecast = App::get_swipe_forecast();
+    let dining = App::get_dining_info();
+
+    println!(
+        "Month Meal Swipe Bill: {}",
+        App::get_monthly_meal_swipe_estimate()
+    );
+    println!(
+        "Month Savings (Sales & Coupons): {}",
+        App::get_monthly_savings()
+    );
+    println!("Semester Meal Swipe Bill: {}", semester.cost);
+    println!(
+        "Semester Meal Swipes Used: {}/{}",
+        forecast.swipes_used,
+        MealPlan::load().semester_swipes
+    );
+    match forecast.run_out_date {
+        _ if forecast.burn_rate.is_none() => {
+            println!("Swipe Forecast: record swipes with the swipe command")
+        }
+        Some(date) => println!(
+            "Swipes Run Out: {} ({} short)",
+            date.format("%Y-%m-%d"),
+            -forecast.projected_leftover
+        ),
+        None => println!(
+            "Projected Leftover Swipes: {}/{}",
+            forecast.projected_leftover, forecast.swipes_remaining
+        ),
+    }
+    println!(
+        "Recommended Swipes/Week: {:.1}",
+        forecast.recommended_per_week
+    );
+    println!(
+        "Dining Dollars Left: {}/{} ({}/day)",
+        dining.remaining, dining.starting_balance, dining.daily_allowance
+    );
+    if let Some(date) = dining.run_out_date {
+        println!("Dining Dollars Run Out: {}", date.format("%Y-%m-%d"));
+    }
+}
+
+// Runs a subcommand, returning true when the TUI should be started afterwards
+pub fn run(command: Command, app: &mut App) -> Result<bool, Box<dyn Error>> {
+    match command {
+        Command::Add {
+            ingredient,
+            price,
+            original_price,
+            discount,
+            tax_rate,
+            date,
+            expended,
+            store,
+            currency,
+            paid_by,
+            split,
+        } => {
+            let expended = match expended {
+                Some(date) => resolve_date(&date)?,
+                None => "NULL".to_string(),
+            };
+            let mut purchase = NewPurchase::new(
+                &ingredient,
+                Money::parse(&price)?,
+                &resolve_date(&date)?,
+                &expended,
+            );
+            purchase.original_price =
+                pricing::parse_optional_amount(&original_price.unwrap_or_default())?;
+            purchase.discount = pricing::parse_optional_amount(&discount.unwrap_or_default())?;
+            purchase.tax_rate = pricing::parse_tax_rate(&tax_rate.unwrap_or_default())?;
+            pricing::validate(purchase.price, purchase.original_price, purchase.discount)?;
+            purchase.paid_by = paid_by;
+            purchase.store = store;
+            purchase.currency = currency::for_purchase(&currency.unwrap_or_default())?;
+            purchase.split_between = parse_names(&split.unwrap_or_default());
+
+            App::insert_purchase(&purchase);
+        }
+        Command::List {
+            filter,
+            sort,
+            view,
+            format,
+        } => {
+            match view {
+                Some(name) => {
+                    let view =
+                        view::get(&name).ok_or_else(|| format!("no view named \"{}\"", name))?;
+                    app.apply_view(&view);
+                    if let Some(err) = app.query_error.take() {
+                        return Err(format!("invalid search in view {}: {}", view.name, err).into());
+                    }
+                }
+                None => apply_search(app, filter, sort)?,
+            }
+
+            match format {
+                ListFormat::Table => print_table(&App::get_ingredient_entries(app)),
+                ListFormat::Csv => export::write_csv(&export::read_rows(app)?, io::stdout())?,
+                ListFormat::Json => export::write_json(&export::read_rows(app)?, io::stdout())?,
+            }
+        }
+        Command::Expend { id, date } => {
+            if !App::update_expended(id, resolve_date(&date)?) {
+                return Err(format!("no transaction with id {}", id).into());
+            }
+        }
+        Command::Delete { id } => {
+            if !App::delete_purchase(id) {
+                return Err(format!("no transaction with id {}", id).into());
+            }
+        }
+        Command::Swipe { count, date } => {
+            if count < 1 {
+                return Err("the swipe count must be at least 1".into());
+            }
+            App::record_swipes(count, &resolve_date(&date)?);
+        }
+        Command::Stats => print_stats(),
+        Command::Plan {
+            swipe_price,
+            swipes_per_day,
+            semester_swipes,
+            start,
+            end,
+        } => {
+            let mut plan = MealPlan::load();
+            let parse_date = |date: &str| {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid date \"{}\", expected YYYY-MM-DD", date))
+            };
+
+            if let Some(swipe_price) = swipe_price {
+                plan.swipe_price = Money::parse(&swipe_price)?;
+            }
+            plan.swipes_per_day = swipes_per_day.unwrap_or(plan.swipes_per_day);
+            plan.semester_swipes = semester_swipes.unwrap_or(plan.semester_swipes);
+            if let Some(start) = start {
+                plan.semester_start = parse_date(&start)?;
+            }
+            if let Some(end) = end {
+                plan.semester_end = parse_date(&end)?;
+            }
+            if plan.semester_end < plan.semester_start {
+                return Err("the semester has to end after it starts".into());
+            }
+            plan.save();
+
+            println!("Profile: {}", db::profile());
+            println!("Swipe Price: {}", plan.swipe_price);
+            println!("Swipes Per Day: {}", plan.swipes_per_day);
+            println!("Semester Swipes: {}", plan.semester_swipes);
+            println!("Semester: {} to {}", plan.semester_start, plan.semester_end);
+        }
+        Command::Currency {
+            symbol,
+            decimal,
+            thousands,
+        } => {
+            let mut format = money::format();
+
+            format.symbol = symbol.unwrap_or(format.symbol);
+            format.decimal_separator = decimal.unwrap_or(format.decimal_separator);
+            if let Some(thousands) = thousands {
+                format.thousands_separator = match thousands.as_str() {
+                    "" | "none" => None,
+                    separator => Some(separator.chars().next().unwrap()),
+                };
+            }
+            format.validate()?;
+            format.save();
+            money::set_format(format);
+
+            println!("Profile: {}", db::profile());
+            println!("Example: {}", Money::from_cents(123456));
+        }
+        Command::Stores => {
+            println!(
+                "{:<32} {:<16} {:>9} {:>9}",
+                "Ingredient", "Store", "Average", "Bought"
+            );
+            for price in App::get_store_prices() {
+                let marker = if price.cheapest { " *" } else { "" };
+                println!(
+                    "{:<32} {:<16} {:>9} {:>9}{}",
+                    price.ingredient, price.store, price.average_price, price.purchases, marker
+                );
+            }
+        }
+        Command::Profiles => {
+            for name in db::profiles() {
+                let marker = if name == db::profile() { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+        }
+        Command::Backup { command } => match command {
+            BackupCommand::Create => {
+                let name = backup::create()?;
+                backup::rotate()?;
+                println!("Created {}", name);
+            }
+            BackupCommand::List => {
+                for name in backup::list() {
+                    println!("{}", name);
+                }
+            }
+            BackupCommand::Restore { name } => {
+                let safety_backup = backup::restore(&name)?;
+                println!(
+                    "Restored {} (previous state saved as {})",
+                    name, safety_backup
+                );
+            }
+            BackupCommand::Retention { count } => {
+                backup::set_retention(count);
+                backup::rotate()?;
+                if count == 0 {
+                    println!("Startup backups disabled");
+                } else {
+                    println!("Keeping {} backups", count);
+                }
+            }
+        },
+        Command::Rates { command } => match command {
+            RatesCommand::List => {
+                let home = currency::home();
+                println!("Home currency: {}", home);
+                for rate in currency::rates() {
+                    println!(
+                        "1 {} = {} {} (updated {})",
+                        rate.currency, rate.rate, home, rate.updated
+                    );
+                }
+                for code in currency::missing_rates() {
+                    println!("1 {} = ? {} (missing, counted at face value)", code, home);
+                }
+            }
+            RatesCommand::Set { currency, rate } => {
+                let currency = currency::normalize(&currency)?;
+                if currency == currency::home() {
+                    return Err(format!("{} is the home currency", currency).into());
+                }
+                currency::set_rate(&currency, rate)?;
+            }
+            RatesCommand::Delete { currency } => {
+                let currency = currency::normalize(&currency)?;
+                if !currency::delete_rate(&currency) {
+                    return Err(format!("no exchange rate for {}", currency).into());
+                }
+            }
+            RatesCommand::Import { path } => {
+                let count = currency::import_rates(&path)?;
+                println!("Imported {} exchange rates", count);
+            }
+            RatesCommand::Home { currency } => {
+                let currency = currency::normalize(&currency)?;
+                currency::set_home(&currency)?;
+                println!(
+                    "Home currency is now {}, exchange rates, the swipe price and dining dollars were converted to it",
+                    currency
+                );
+            }
+        },
+        Command::Report {
+            period,
+            month,
+            week,
+            format,
+            output,
+        } => {
+            let date = match (period, month, week) {
+                (Period::Week, Some(_), _) => {
+                    return Err("--month only applies to --period month".into())
+                }
+                (Period::Month, _, Some(_)) => {
+                    return Err("--week only applies to --period week".into())
+                }
+                (Period::Month, Some(month), _) => {
+                    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                        .map_err(|_| format!("invalid month \"{}\", expected YYYY-MM", month))?
+                }
+                (Period::Week, _, Some(week)) => NaiveDate::parse_from_str(&week, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid week \"{}\", expected YYYY-MM-DD", week))?,
+                _ => Local::now().date_naive(),
+            };
+
+            let report = report::build(period, date);
+            let writer: Box<dyn io::Write> = match output {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(io::stdout()),
+            };
+
+            match format {
+                ReportFormat::Markdown => report::write_markdown(&report, writer)?,
+                ReportFormat::Html => report::write_html(&report, writer)?,
+            }
+        }
+        Command::Export {
+            format,
+            output,
+            account,
+            search,
+            sort,
+        } => {
+            apply_search(app, search, sort)?;
+
+            let rows = export::read_rows(app)?;
+            let writer: Box<dyn io::Write> = match output {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(io::stdout()),
+            };
+
+            match format {
+                ExportFormat::Csv => export::write_csv(&rows, writer)?,
+                ExportFormat::Json => export::write_json(&rows, writer)?,
+                ExportFormat::Ledger => export::write_ledger(&rows, &account, writer)?,
+            }
+            eprintln!("Exported {} transactions", rows.len());
+        }
+        Command::Import {
+            file,
+            ingredient,
+            price,
+            purchase_date,
+            expended_date,
+            yes,
+        } => {
+            let mapping = ColumnMapping {
+                ingredient,
+                price,
+                purchase_date,
+                expended_date,
+            };
+            let rows = import::read_csv(&file, &mapping)?;
+
+            if !yes {
+                app.import_rows = rows;
+                app.current_screen = CurrentScreen::ImportPreview;
+                return Ok(true);
+            }
+
+            let count = import::commit(&rows);
+            eprintln!(
+                "Imported {} transactions, skipped {} duplicates or credits",
+                count,
+                rows.len() - count
+            );
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,250 @@
+use crate::app::Transaction;
+use crate::category::{self, categorize};
+use crate::currency::{self, home_price_sql};
+use crate::db;
+use crate::money::Money;
+use crate::pricing::SAVINGS_SQL;
+
+/// SQL for the days between purchase and expended date, NULL while unexpended
+const DAYS_LASTED_SQL: &str = "(CASE WHEN expendedDate = 'NULL' THEN NULL ELSE julianday(expendedDate) - julianday(purchaseDate) END)";
+
+/// Columns the transaction table can show, the last four derived from the stored ones
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Ingredient,
+    Price,
+    Store,
+    PurchaseDate,
+    ExpendedDate,
+    DaysLasted,
+    Category,
+    CostPerDay,
+    Savings,
+}
+
+impl Column {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::Ingredient => "Ingredient",
+            Column::Price => "Net Price",
+            Column::Store => "Store",
+            Column::PurchaseDate => "Purchase Date",
+            Column::ExpendedDate => "Expended Date",
+            Column::DaysLasted => "Days Lasted",
+            Column::Category => "Category",
+            Column::CostPerDay => "Cost/Day",
+            Column::Savings => "Saved",
+        }
+    }
+
+    // Expression to sort this column by
+    pub fn sql(&self) -> String {
+        match self {
+            Column::Id => "rowid".to_string(),
+            Column::Ingredient => "ingredient COLLATE NOCASE".to_string(),
+            Column::Price => home_price_sql(),
+            Column::Store => "store COLLATE NOCASE".to_string(),
+            Column::PurchaseDate => "purchaseDate".to_string(),
+            Column::ExpendedDate => "expendedDate".to_string(),
+            Column::DaysLasted => DAYS_LASTED_SQL.to_string(),
+            Column::Category => category::sql_expression("ingredient"),
+            Column::CostPerDay => format!("{} / max({}, 1)", home_price_sql(), DAYS_LASTED_SQL),
+            Column::Savings => currency::home_sql(SAVINGS_SQL),
+        }
+    }
+
+    // Name used when saving sorts and column settings
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Ingredient => "ingredient",
+            Column::Price => "price",
+            Column::Store => "store",
+            Column::PurchaseDate => "purchaseDate",
+            Column::ExpendedDate => "expendedDate",
+            Column::DaysLasted => "daysLasted",
+            Column::Category => "category",
+            Column::CostPerDay => "costPerDay",
+            Column::Savings => "savings",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Column> {
+        match name.to_lowercase().as_str() {
+            "id" | "rowid" => Some(Column::Id),
+            "ingredient" | "name" => Some(Column::Ingredient),
+            "price" => Some(Column::Price),
+            "store" => Some(Column::Store),
+            "purchasedate" | "purchased" | "date" => Some(Column::PurchaseDate),
+            "expendeddate" | "expended" => Some(Column::ExpendedDate),
+            "dayslasted" | "days" => Some(Column::DaysLasted),
+            "category" | "cat" => Some(Column::Category),
+            "costperday" => Some(Column::CostPerDay),
+            "savings" | "saved" => Some(Column::Savings),
+            _ => None,
+        }
+    }
+
+    // Cell text for this column of a transaction
+    pub fn value(&self, row: &Transaction) -> String {
+        match self {
+            Column::Id => row.id.to_string(),
+            Column::Ingredient => row.ingredient.clone(),
+            Column::Price => row.price_text(),
+            Column::Store => row.store.clone().unwrap_or_default(),
+            Column::PurchaseDate => row.purchase_text(),
+            Column::ExpendedDate => row.expended_text(),
+            Column::DaysLasted => row
+                .days_lasted()
+                .map(|days| days.to_string())
+                .unwrap_or_default(),
+            Column::Category => categorize(&row.ingredient).to_string(),
+            Column::CostPerDay => row
+                .days_lasted()
+                .map(|days| (row.home_price / days.max(1)).to_string())
+                .unwrap_or_default(),
+            Column::Savings => Some(row.savings())
+                .filter(|savings| *savings > Money::ZERO)
+                .map(|savings| row.amount_text(savings))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ColumnSetting {
+    pub column: Column,
+    pub visible: bool,
+    /// Width in characters, 0 to share the leftover space with the other 0 width columns
+    pub width: u16,
+}
+
+/// Order, visibility and width of every table column, saved per profile
+#[derive(Clone)]
+pub struct ColumnSettings {
+    pub columns: Vec<ColumnSetting>,
+}
+
+impl Default for ColumnSettings {
+    fn default() -> ColumnSettings {
+        let setting = |column, visible, width| ColumnSetting {
+            column,
+            visible,
+            width,
+        };
+
+        ColumnSettings {
+            columns: vec![
+                setting(Column::Id, true, 6),
+                setting(Column::Ingredient, true, 0),
+                setting(Column::Price, true, 10),
+                setting(Column::Store, true, 0),
+                setting(Column::PurchaseDate, true, 14),
+                setting(Column::ExpendedDate, true, 14),
+                setting(Column::DaysLasted, false, 12),
+                setting(Column::Category, false, 12),
+                setting(Column::CostPerDay, false, 10),
+                setting(Column::Savings, false, 10),
+            ],
+        }
+    }
+}
+
+impl ColumnSettings {
+    // Saved settings, written as "id:6,ingredient:0,-category:12" where "-" hides a column
+    pub fn load() -> ColumnSettings {
+        let Some(saved) = db::get_setting("columns") else {
+            return ColumnSettings::default();
+        };
+        let mut columns = Vec::<ColumnSetting>::new();
+
+        for entry in saved.split(',') {
+            let (name, width) = entry.split_once(':').unwrap_or((entry, "0"));
+            let visible = !name.starts_with('-');
+            let Some(column) = Column::parse(name.trim_start_matches('-')) else {
+                continue;
+            };
+            if columns.iter().all(|setting| setting.column != column) {
+                columns.push(ColumnSetting {
+                    column,
+                    visible,
+                    width: width.parse().unwrap_or(0),
+                });
+            }
+        }
+
+        // Columns added after the settings were saved go at the end, hidden
+        for setting in ColumnSettings::default().columns {
+            if columns.iter().all(|saved| saved.column != setting.column) {
+                columns.push(ColumnSetting {
+                    visible: false,
+                    ..setting
+                });
+            }
+        }
+
+        ColumnSettings { columns }
+    }
+
+    pub fn save(&self) {
+        let entries: Vec<String> = self
+            .columns
+            .iter()
+            .map(|setting| {
+                let hidden = if setting.visible { "" } else { "-" };
+                format!("{}{}:{}", hidden, setting.column.name(), setting.width)
+            })
+            .collect();
+
+        db::set_setting("columns", &entries.join(","));
+    }
+
+    pub fn visible(&self) -> Vec<ColumnSetting> {
+        self.columns
+            .iter()
+            .filter(|setting| setting.visible)
+            .copied()
+            .collect()
+    }
+
+    // Hides or shows a column, always leaving at least one visible. Returns whether it changed
+    pub fn toggle(&mut self, i: usize) -> bool {
+        let visible_count = self.visible().len();
+        match self.columns.get_mut(i) {
+            Some(setting) if !setting.visible || visible_count > 1 => {
+                setting.visible = !setting.visible;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Returns whether the width changed, it stays within 0 and 60
+    pub fn resize(&mut self, i: usize, change: i16) -> bool {
+        let Some(setting) = self.columns.get_mut(i) else {
+            return false;
+        };
+        let width = setting.width.saturating_add_signed(change).min(60);
+        let changed = width != setting.width;
+        setting.width = width;
+        changed
+    }
+
+    // Moves a column one place up or down, returning its new index
+    pub fn shift(&mut self, i: usize, up: bool) -> usize {
+        let target = if up {
+            i.checked_sub(1)
+        } else {
+            Some(i + 1).filter(|&j| j < self.columns.len())
+        };
+        match target {
+            Some(j) => {
+                self.columns.swap(i, j);
+                j
+            }
+            None => i,
+        }
+    }
+}
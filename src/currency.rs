@@ -0,0 +1,348 @@
+use std::error::Error;
+use std::fs::File;
+
+use chrono::Local;
+use sqlite::{Connection, State};
+
+use crate::app::MealPlan;
+use crate::audit;
+use crate::db;
+use crate::money::Money;
+use crate::pricing::NET_PRICE_SQL;
+
+/// Home currency until one is set, statistics are converted into the home currency
+const DEFAULT_HOME: &str = "USD";
+
+// SQL converting an amount in cents of a purchase's currency into home currency cents. Purchases
+// in the home currency store a NULL currency, a currency without an exchange rate counts at face
+// value until one is entered
+pub fn home_sql(cents: &str) -> String {
+    format!(
+        "(CASE WHEN purchase.currency IS NULL THEN {0} \
+        ELSE CAST(round({0} * coalesce((SELECT rate FROM exchange_rate WHERE exchange_rate.currency = purchase.currency), 1)) AS INTEGER) END)",
+        cents
+    )
+}
+
+// SQL for what was paid for a purchase in home currency cents
+pub fn home_price_sql() -> String {
+    home_sql(NET_PRICE_SQL)
+}
+
+// SQL for the home currency's code, read when the query runs so saved searches follow a change
+pub fn home_code_sql() -> String {
+    format!(
+        "coalesce((SELECT value FROM setting WHERE key = 'home_currency'), '{}')",
+        DEFAULT_HOME
+    )
+}
+
+pub struct ExchangeRate {
+    pub currency: String,
+    /// Home currency units one unit of `currency` is worth
+    pub rate: f64,
+    pub updated: String,
+}
+
+pub fn home() -> String {
+    db::get_setting("home_currency").unwrap_or(DEFAULT_HOME.to_string())
+}
+
+// Changes the home currency, keeping every purchase in the currency it was made in. The exchange
+// rates are rebased onto the new home currency, so it needs a rate before anything is changed, and
+// the meal plan and dining dollar amounts are converted into it. Every changed row is recorded in
+// the audit log
+pub fn set_home(code: &str) -> Result<(), String> {
+    let previous = home();
+    if previous == code {
+        return Ok(());
+    }
+
+    let Some(rate) = rates()
+        .into_iter()
+        .find(|rate| rate.currency == code)
+        .map(|rate| rate.rate)
+    else {
+        return Err(format!(
+            "no exchange rate for {}, set one before making it the home currency",
+            code
+        ));
+    };
+
+    let conn = db::open();
+    let transaction = db::Transaction::begin(&conn);
+
+    let mut statement = conn
+        .prepare("UPDATE exchange_rate SET rate = rate / ?")
+        .unwrap();
+    statement.bind((1, rate)).unwrap();
+    statement.next().unwrap();
+
+    let mut statement = conn
+        .prepare("DELETE FROM exchange_rate WHERE currency = ?")
+        .unwrap();
+    statement.bind((1, code)).unwrap();
+    statement.next().unwrap();
+    write_rate(&conn, &previous, 1.0 / rate);
+
+    // Home currency purchases store no currency, so both sides of the switch are rewritten
+    let mut statement = conn
+        .prepare("SELECT rowid FROM purchase WHERE currency IS NULL OR currency = ?")
+        .unwrap();
+    statement.bind((1, code)).unwrap();
+    let mut changed = Vec::<(i64, Option<String>)>::new();
+    while let State::Row = statement.next().unwrap() {
+        let id = statement.read::<i64, _>("rowid").unwrap();
+        changed.push((id, audit::snapshot(&conn, id)));
+    }
+
+    let mut statement = conn
+        .prepare("UPDATE purchase SET currency = CASE WHEN currency IS NULL THEN ? ELSE NULL END WHERE currency IS NULL OR currency = ?")
+        .unwrap();
+    statement.bind((1, previous.as_str())).unwrap();
+    statement.bind((2, code)).unwrap();
+    statement.next().unwrap();
+
+    for (id, before) in changed {
+        audit::record(&conn, "update", id, before, audit::snapshot(&conn, id));
+    }
+
+    convert_home_amounts(&conn, rate);
+    db::write_setting(&conn, "home_currency", code);
+    transaction.commit();
+    Ok(())
+}
+
+// Converts the amounts stored in the home currency without a currency of their own, the swipe
+// price, the dining dollars balance and dining transactions, `rate` being what one unit of the new
+// home currency was worth in the old one
+fn convert_home_amounts(conn: &Connection, rate: f64) {
+    let mut statement = conn
+        .prepare("SELECT rowid, amount FROM dining_transaction")
+        .unwrap();
+    let mut amounts = Vec::<(i64, Money)>::new();
+    while let State::Row = statement.next().unwrap() {
+        amounts.push((
+            statement.read::<i64, _>("rowid").unwrap(),
+            Money::from_cents(statement.read::<i64, _>("amount").unwrap_or(0)),
+        ));
+    }
+
+    for (id, amount) in amounts {
+        let before = audit::snapshot_row(conn, "dining_transaction", id);
+        let mut statement = conn
+            .prepare("UPDATE dining_transaction SET amount = ? WHERE rowid = ?")
+            .unwrap();
+        statement.bind((1, convert(amount, rate).cents())).unwrap();
+        statement.bind((2, id)).unwrap();
+        statement.next().unwrap();
+        let after = audit::snapshot_row(conn, "dining_transaction", id);
+        audit::record_row(conn, "dining_transaction", "update", id, before, after);
+    }
+
+    // The swipe price is saved as a plain amount and the dining balance in cents. A swipe price
+    // never set uses the default, which is in the old home currency just the same
+    convert_setting(
+        conn,
+        "swipe_price",
+        rate,
+        Some(MealPlan::default().swipe_price),
+        |value| Money::parse(value).ok(),
+        Money::plain,
+    );
+    convert_setting(
+        conn,
+        "dining_starting_balance",
+        rate,
+        None,
+        |value| value.parse().ok().map(Money::from_cents),
+        |amount| amount.cents().to_string(),
+    );
+}
+
+fn convert_setting(
+    conn: &Connection,
+    key: &str,
+    rate: f64,
+    default: Option<Money>,
+    read: fn(&str) -> Option<Money>,
+    write: fn(Money) -> String,
+) {
+    let rowid = |conn: &Connection| {
+        let mut statement = conn
+            .prepare("SELECT rowid FROM setting WHERE key = ?")
+            .unwrap();
+        statement.bind((1, key)).unwrap();
+        match statement.next().unwrap() {
+            State::Row => statement.read::<i64, _>("rowid").ok(),
+            State::Done => None,
+        }
+    };
+
+    let before = rowid(conn).and_then(|id| audit::snapshot_row(conn, "setting", id));
+    let amount = match db::read_setting(conn, key) {
+        Some(value) => read(&value),
+        None => default,
+    };
+    let Some(amount) = amount else {
+        return;
+    };
+
+    db::write_setting(conn, key, &write(convert(amount, rate)));
+    let id = rowid(conn).unwrap();
+    let operation = if before.is_some() { "update" } else { "insert" };
+    let after = audit::snapshot_row(conn, "setting", id);
+    audit::record_row(conn, "setting", operation, id, before, after);
+}
+
+fn convert(amount: Money, rate: f64) -> Money {
+    Money::from_cents((amount.cents() as f64 / rate).round() as i64)
+}
+
+// An amount in `currency` converted into the home currency, counted at face value like `home_sql`
+// when the currency has no exchange rate
+pub fn to_home(amount: Money, currency: Option<&str>) -> Money {
+    let Some(currency) = currency else {
+        return amount;
+    };
+    match rates().into_iter().find(|rate| rate.currency == currency) {
+        Some(rate) => Money::from_cents((amount.cents() as f64 * rate.rate).round() as i64),
+        None => amount,
+    }
+}
+
+// ISO 4217 style code such as "EUR", upper cased
+pub fn normalize(code: &str) -> Result<String, String> {
+    let code = code.trim().to_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code)
+    } else {
+        Err(format!(
+            "invalid currency \"{}\", expected a three letter code like EUR",
+            code
+        ))
+    }
+}
+
+// Currency to store for a purchase, None for the home currency or a blank input
+pub fn for_purchase(code: &str) -> Result<Option<String>, String> {
+    if code.trim().is_empty() {
+        return Ok(None);
+    }
+    let code = normalize(code)?;
+    Ok(if code == home() { None } else { Some(code) })
+}
+
+pub fn rates() -> Vec<ExchangeRate> {
+    let conn = db::open();
+    let mut statement = conn
+        .prepare("SELECT * FROM exchange_rate ORDER BY currency")
+        .unwrap();
+    let mut rates = Vec::<ExchangeRate>::new();
+
+    while let State::Row = statement.next().unwrap() {
+        rates.push(ExchangeRate {
+            currency: statement.read::<String, _>("currency").unwrap(),
+            rate: statement.read::<f64, _>("rate").unwrap(),
+            updated: statement.read::<String, _>("updated").unwrap_or_default(),
+        });
+    }
+
+    rates
+}
+
+pub fn set_rate(currency: &str, rate: f64) -> Result<(), String> {
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!(
+            "invalid rate {} for {}, rates must be positive",
+            rate, currency
+        ));
+    }
+
+    write_rate(&db::open(), currency, rate);
+    Ok(())
+}
+
+fn write_rate(conn: &Connection, currency: &str, rate: f64) {
+    let mut statement = conn
+        .prepare("INSERT OR REPLACE INTO exchange_rate (currency, rate, updated) VALUES (?, ?, ?)")
+        .unwrap();
+    statement.bind((1, currency)).unwrap();
+    statement.bind((2, rate)).unwrap();
+    statement
+        .bind((3, Local::now().format("%Y-%m-%d").to_string().as_str()))
+        .unwrap();
+    statement.next().unwrap();
+}
+
+// Returns whether a rate for that currency existed
+pub fn delete_rate(currency: &str) -> bool {
+    let conn = db::open();
+    let mut statement = conn
+        .prepare("DELETE FROM exchange_rate WHERE currency = ?")
+        .unwrap();
+    statement.bind((1, currency)).unwrap();
+    statement.next().unwrap();
+    conn.change_count() > 0
+}
+
+// Reads "currency,rate" rows, taking the columns by those header names or else the first two columns
+pub fn import_rates(path: &str) -> Result<usize, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(File::open(path)?);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str, fallback: usize| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+            .unwrap_or(fallback)
+    };
+    let (currency_column, rate_column) = (column("currency", 0), column("rate", 1));
+
+    let mut rates = Vec::<(String, f64)>::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |index: usize| record.get(index).unwrap_or_default().trim().to_string();
+        let currency =
+            normalize(&field(currency_column)).map_err(|err| format!("line {}: {}", i + 2, err))?;
+        let rate = field(rate_column)
+            .parse::<f64>()
+            .map_err(|_| format!("line {}: invalid rate \"{}\"", i + 2, field(rate_column)))?;
+        rates.push((currency, rate));
+    }
+
+    // Check every row before saving any so a bad file changes nothing
+    let home = home();
+    let rates: Vec<(String, f64)> = rates
+        .into_iter()
+        .filter(|(currency, _)| *currency != home)
+        .collect();
+    if let Some((currency, rate)) = rates
+        .iter()
+        .find(|(_, rate)| !rate.is_finite() || *rate <= 0.0)
+    {
+        return Err(format!(
+            "invalid rate {} for {}, rates must be positive",
+            rate, currency
+        )
+        .into());
+    }
+    for (currency, rate) in &rates {
+        set_rate(currency, *rate)?;
+    }
+
+    Ok(rates.len())
+}
+
+// Currencies purchases were made in that have no exchange rate yet
+pub fn missing_rates() -> Vec<String> {
+    let conn = db::open();
+    let query = "SELECT DISTINCT currency FROM purchase WHERE currency IS NOT NULL AND currency NOT IN (SELECT currency FROM exchange_rate) ORDER BY currency";
+    let mut statement = conn.prepare(query).unwrap();
+    let mut missing = Vec::<String>::new();
+
+    while let State::Row = statement.next().unwrap() {
+        missing.push(statement.read::<String, _>("currency").unwrap());
+    }
+
+    missing
+}
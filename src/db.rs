@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use sqlite::{Connection, State};
+
+use crate::category;
+use crate::fuzzy;
+use crate::money::{self, MoneyFormat};
+use crate::view;
+
+pub const DB_PATH: &str = "src/purchases.db";
+pub const PROFILE_DIR: &str = "src/profiles";
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Profile whose database `open` uses, set once from `--profile` and again when switching in the TUI
+static PROFILE: RwLock<String> = RwLock::new(String::new());
+
+pub fn profile() -> String {
+    let profile = PROFILE.read().unwrap();
+    if profile.is_empty() {
+        DEFAULT_PROFILE.to_string()
+    } else {
+        profile.clone()
+    }
+}
+
+// Switches every following `open` to the profile's database, creating it if needed
+pub fn set_profile(name: &str) {
+    *PROFILE.write().unwrap() = name.to_string();
+    init();
+}
+
+// Profile names become file names, so only letters, digits, '-' and '_' are allowed
+pub fn is_valid_profile(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// The default profile keeps the original database so existing data stays where it was
+pub fn path() -> PathBuf {
+    match profile().as_str() {
+        DEFAULT_PROFILE => PathBuf::from(DB_PATH),
+        name => PathBuf::from(PROFILE_DIR).join(format!("{}.db", name)),
+    }
+}
+
+// Every profile with a database, the default profile first
+pub fn profiles() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(PROFILE_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .strip_suffix(".db")
+                        .map(str::to_string)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+// Opens the current profile's purchases database
+pub fn open() -> Connection {
+    let conn = sqlite::open(path()).unwrap();
+    fuzzy::register(&conn).unwrap();
+    category::register(&conn).unwrap();
+    conn
+}
+
+/// Groups statements so they are written together or not at all, rolled back unless committed
+pub struct Transaction<'a> {
+    conn: &'a Connection,
+    committed: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn begin(conn: &'a Connection) -> Transaction<'a> {
+        conn.execute("BEGIN").unwrap();
+        Transaction {
+            conn,
+            committed: false,
+        }
+    }
+
+    pub fn commit(mut self) {
+        self.conn.execute("COMMIT").unwrap();
+        self.committed = true;
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.conn.execute("ROLLBACK");
+        }
+    }
+}
+
+// Creates any tables missing from the database, run once on startup
+pub fn init() {
+    if let Some(parent) = path().parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    let conn = open();
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS purchase (
+            ingredient varchar(1024),
+            price int,
+            purchaseDate date,
+            expendedDate date
+        );
+        CREATE TABLE IF NOT EXISTS meal (
+            mealDate date,
+            servings int
+        );
+        CREATE TABLE IF NOT EXISTS swipe (
+            swipeDate date,
+            swipes int
+        );
+        CREATE TABLE IF NOT EXISTS dining_transaction (
+            location varchar(1024),
+            amount int,
+            transactionDate date
+        );
+        CREATE TABLE IF NOT EXISTS template (
+            name varchar(1024) PRIMARY KEY,
+            everyDays int,
+            lastApplied date
+        );
+        CREATE TABLE IF NOT EXISTS template_item (
+            template varchar(1024),
+            ingredient varchar(1024)
+        );
+        CREATE TABLE IF NOT EXISTS audit (
+            operation varchar(16),
+            purchaseId int,
+            beforeValue text,
+            afterValue text,
+            changedAt datetime
+        );
+        CREATE TABLE IF NOT EXISTS person (
+            name varchar(256) PRIMARY KEY
+        );
+        CREATE TABLE IF NOT EXISTS store (
+            name varchar(256) PRIMARY KEY COLLATE NOCASE
+        );
+        CREATE TABLE IF NOT EXISTS view (
+            name varchar(256) PRIMARY KEY,
+            search varchar(1024),
+            orderBy varchar(256)
+        );
+        CREATE TABLE IF NOT EXISTS setting (
+            key varchar(256) PRIMARY KEY,
+            value varchar(1024)
+        );
+        CREATE TABLE IF NOT EXISTS exchange_rate (
+            currency varchar(3) PRIMARY KEY,
+            rate real,
+            updated date
+        );
+        ",
+    )
+    .unwrap();
+
+    add_column(&conn, "purchase", "paidBy", "varchar(256)");
+    add_column(&conn, "purchase", "splitBetween", "varchar(1024)");
+    add_column(&conn, "purchase", "store", "varchar(256)");
+    add_column(&conn, "purchase", "currency", "varchar(3)");
+    add_column(&conn, "purchase", "originalPrice", "int");
+    add_column(&conn, "purchase", "discount", "int");
+    add_column(&conn, "purchase", "taxRate", "real");
+    add_column(&conn, "audit", "tableName", "varchar(64)");
+
+    view::seed_defaults(&conn);
+
+    money::set_format(MoneyFormat::load());
+}
+
+// Adds a column to an existing table unless a previous run already added it
+fn add_column(conn: &Connection, table: &str, column: &str, column_type: &str) {
+    let mut statement = conn
+        .prepare(format!("PRAGMA table_info({})", table))
+        .unwrap();
+    while let State::Row = statement.next().unwrap() {
+        if statement.read::<String, _>("name").unwrap() == column {
+            return;
+        }
+    }
+
+    conn.execute(format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        table, column, column_type
+    ))
+    .unwrap();
+}
+
+pub fn get_setting(key: &str) -> Option<String> {
+    read_setting(&open(), key)
+}
+
+// Reads a setting on an existing connection, so it can be part of a transaction
+pub fn read_setting(conn: &Connection, key: &str) -> Option<String> {
+    let mut statement = conn
+        .prepare("SELECT value FROM setting WHERE key = ?")
+        .unwrap();
+    statement.bind((1, key)).unwrap();
+
+    match statement.next().unwrap() {
+        State::Row => statement.read::<String, _>("value").ok(),
+        State::Done => None,
+    }
+}
+
+// Every saved setting by key, read in one query for callers that need several
+pub fn settings() -> HashMap<String, String> {
+    let conn = open();
+    let mut statement = conn.prepare("SELECT key, value FROM setting").unwrap();
+    let mut settings = HashMap::new();
+
+    while let State::Row = statement.next().unwrap() {
+        settings.insert(
+            statement.read::<String, _>("key").unwrap(),
+            statement.read::<String, _>("value").unwrap_or_default(),
+        );
+    }
+
+    settings
+}
+
+pub fn set_setting(key: &str, value: &str) {
+    write_setting(&open(), key, value);
+}
+
+// Saves a setting on an existing connection, so it can be part of a transaction
+pub fn write_setting(conn: &Connection, key: &str, value: &str) {
+    let mut statement = conn
+        .prepare("INSERT INTO setting (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .unwrap();
+    statement.bind((1, key)).unwrap();
+    statement.bind((2, value)).unwrap();
+
+    statement.next().unwrap();
+}
@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::io::Write;
+
+use chrono::NaiveDate;
+use serde_json::json;
+use sqlite::State;
+
+use crate::app::{App, Transaction};
+use crate::category::categorize;
+use crate::currency;
+use crate::db;
+use crate::money::{self, Money};
+
+// ISO 8601 date, None for a missing or unexpended date
+fn iso_date(date: Option<NaiveDate>) -> Option<String> {
+    date.map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+// ISO 8601 date, or the stored text when it does not parse. None for a missing or unexpended date
+fn date_text(date: Option<NaiveDate>, text: &Option<String>) -> Option<String> {
+    iso_date(date).or_else(|| {
+        text.clone()
+            .filter(|text| !text.trim().is_empty() && text != "NULL")
+    })
+}
+
+// Reads the rows matching the app's current search and sort
+pub fn read_rows(app: &App) -> Result<Vec<Transaction>, Box<dyn Error>> {
+    let conn = db::open();
+    let mut statement = app.prepare_entries(&conn, None)?;
+    let mut rows = Vec::<Transaction>::new();
+
+    while let State::Row = statement.next()? {
+        rows.push(Transaction::read(&statement)?);
+    }
+
+    Ok(rows)
+}
+
+pub fn write_csv<W: Write>(rows: &[Transaction], writer: W) -> Result<(), Box<dyn Error>> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    let home = currency::home();
+
+    csv_writer.write_record([
+        "id",
+        "ingredient",
+        "price",
+        "currency",
+        "original_price",
+        "discount",
+        "tax_rate",
+        "net_price",
+        "store",
+        "paid_by",
+        "split_between",
+        "purchase_date",
+        "expended_date",
+    ])?;
+    for row in rows {
+        csv_writer.write_record([
+            row.id.to_string(),
+            row.ingredient.clone(),
+            row.price.plain(),
+            row.currency.clone().unwrap_or(home.clone()),
+            row.original_price.map(Money::plain).unwrap_or_default(),
+            row.discount.map(Money::plain).unwrap_or_default(),
+            row.tax_rate
+                .map(|rate| rate.to_string())
+                .unwrap_or_default(),
+            row.net_price().plain(),
+            row.store.clone().unwrap_or_default(),
+            row.paid_by.clone().unwrap_or_default(),
+            row.split_between.join(","),
+            date_text(row.purchase_date, &row.purchase_date_text).unwrap_or_default(),
+            date_text(row.expended_date, &row.expended_date_text).unwrap_or_default(),
+        ])?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+pub fn write_json<W: Write>(rows: &[Transaction], mut writer: W) -> Result<(), Box<dyn Error>> {
+    let home = currency::home();
+    let transactions: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            json!({
+                "id": row.id,
+                "ingredient": row.ingredient,
+                "price_cents": row.price.cents(),
+                "currency": row.currency.as_ref().unwrap_or(&home),
+                "original_price_cents": row.original_price.map(Money::cents),
+                "discount_cents": row.discount.map(Money::cents),
+                "tax_rate": row.tax_rate,
+                "net_price_cents": row.net_price().cents(),
+                "store": row.store,
+                "paid_by": row.paid_by,
+                "split_between": row.split_between,
+                "purchase_date": date_text(row.purchase_date, &row.purchase_date_text),
+                "expended_date": date_text(row.expended_date, &row.expended_date_text),
+            })
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut writer, &transactions)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+// Writes one journal transaction per purchase date, balanced against `account`
+pub fn write_ledger<W: Write>(
+    rows: &[Transaction],
+    account: &str,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut dated: Vec<&Transaction> = rows
+        .iter()
+        .filter(|row| row.purchase_date.is_some())
+        .collect();
+    dated.sort_by_key(|row| row.purchase_date);
+
+    for (i, row) in dated.iter().enumerate() {
+        let date = iso_date(row.purchase_date).unwrap_or_default();
+
+        if i == 0 || dated[i - 1].purchase_date != row.purchase_date {
+            writeln!(writer, "{} Groceries", date)?;
+        }
+
+        let posting = format!("expenses:food:groceries:{}", categorize(&row.ingredient));
+        // Ledger reads "." as the decimal point whatever the display format is, and records what was paid
+        let amount = match &row.currency {
+            Some(code) => format!("{} {}", row.net_price().plain(), code),
+            None => format!("{}{}", money::format().symbol, row.net_price().plain()),
+        };
+        writeln!(
+            writer,
+            "    {:<40}  {:>10}  ; {}",
+            posting, amount, row.ingredient
+        )?;
+
+        if i + 1 == dated.len() || dated[i + 1].purchase_date != row.purchase_date {
+            writeln!(writer, "    {}", account)?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Default file name for exports started from the TUI
+pub fn default_export_path(extension: &str) -> String {
+    format!(
+        "transactions-{}.{}",
+        chrono::Local::now().format("%Y-%m-%d"),
+        extension
+    )
+}
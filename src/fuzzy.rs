@@ -0,0 +1,342 @@
+use std::ffi::{c_char, c_int, CStr};
+
+use sqlite::{ffi, Connection};
+
+/// Points for each matched character
+const MATCH_SCORE: i64 = 16;
+/// Extra points when a match directly follows the previous one
+const CONSECUTIVE_BONUS: i64 = 12;
+/// Extra points for matching the first letter of a word, so "chkn" prefers "Chicken" over "Snack Nuts"
+const WORD_START_BONUS: i64 = 8;
+/// Points lost per skipped character before the first match and between matches
+const GAP_PENALTY: i64 = 1;
+/// Points lost per typo when the pattern only matches a word with typos, enough that any
+/// in-order match of the same pattern ranks higher
+const TYPO_PENALTY: i64 = 32;
+
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Character (not byte) indices of the matched characters in the text
+    pub positions: Vec<usize>,
+}
+
+// Matches the pattern's characters in order anywhere in the text, ignoring case, and returns the
+// best scoring alignment. Patterns that are not in the text in order, such as "chikcen" or
+// "chiken", still match a word within `max_typos` edits, otherwise the result is `None`
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    // Cheap check first, most rows of a search do not contain the pattern at all
+    let mut remaining = lower.iter();
+    if pattern.iter().all(|p| remaining.any(|c| c == p)) {
+        subsequence_match(&pattern, &text, &lower)
+    } else {
+        typo_match(&pattern, &lower)
+    }
+}
+
+fn subsequence_match(pattern: &[char], text: &[char], lower: &[char]) -> Option<FuzzyMatch> {
+    // best[i][j]: highest score with pattern[..=i] matched and pattern[i] on text[j]
+    let mut best = vec![vec![None::<i64>; text.len()]; pattern.len()];
+    let mut previous = vec![vec![0usize; text.len()]; pattern.len()];
+
+    for (i, &p) in pattern.iter().enumerate() {
+        // Best of best[i - 1][k] + GAP_PENALTY * k over k < j - 1, so a gapped link costs one lookup
+        let mut gapped: Option<(i64, usize)> = None;
+
+        for j in 0..text.len() {
+            if i > 0 && j >= 2 {
+                if let Some(before) = best[i - 1][j - 2] {
+                    let adjusted = before + GAP_PENALTY * (j - 2) as i64;
+                    if gapped.is_none_or(|(current, _)| adjusted > current) {
+                        gapped = Some((adjusted, j - 2));
+                    }
+                }
+            }
+            if lower[j] != p {
+                continue;
+            }
+            let word_start = j == 0 || !text[j - 1].is_alphanumeric();
+            let own = MATCH_SCORE + if word_start { WORD_START_BONUS } else { 0 };
+
+            if i == 0 {
+                best[i][j] = Some(own - GAP_PENALTY * j as i64);
+                continue;
+            }
+            if let Some((adjusted, k)) = gapped {
+                best[i][j] = Some(adjusted - GAP_PENALTY * (j - 1) as i64 + own);
+                previous[i][j] = k;
+            }
+            if let Some(before) = j.checked_sub(1).and_then(|k| best[i - 1][k]) {
+                let score = before + own + CONSECUTIVE_BONUS;
+                if best[i][j].is_none_or(|current| score > current) {
+                    best[i][j] = Some(score);
+                    previous[i][j] = j - 1;
+                }
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|(_, score)| *score)?;
+
+    let mut positions = vec![j];
+    for i in (1..=last).rev() {
+        j = previous[i][j];
+        positions.push(j);
+    }
+    positions.reverse();
+
+    Some(FuzzyMatch { score, positions })
+}
+
+// Typos allowed for a pattern, none for short ones where a single edit matches too much
+fn max_typos(pattern_len: usize) -> usize {
+    match pattern_len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Matches the pattern against each word of the text, or the start of a word, allowing a few
+// insertions, deletions, substitutions and swapped neighbours
+fn typo_match(pattern: &[char], lower: &[char]) -> Option<FuzzyMatch> {
+    let max = max_typos(pattern.len());
+    if max == 0 {
+        return None;
+    }
+
+    let mut found: Option<(usize, usize, usize)> = None;
+    let mut start = 0;
+    while start < lower.len() {
+        if !lower[start].is_alphanumeric() {
+            start += 1;
+            continue;
+        }
+        let end = (start..lower.len())
+            .find(|&j| !lower[j].is_alphanumeric())
+            .unwrap_or(lower.len());
+
+        // The whole word, or a prefix of about the pattern's length so "chikn" matches "chicken breast"
+        let shortest = (start + pattern.len().saturating_sub(max)).max(start + 1);
+        for prefix_end in shortest..=end.min(start + pattern.len() + max) {
+            let distance = edit_distance(pattern, &lower[start..prefix_end], max);
+            if distance.is_some_and(|distance| found.is_none_or(|(best, _, _)| distance < best)) {
+                found = Some((distance.unwrap(), start, prefix_end));
+            }
+        }
+        start = end;
+    }
+
+    let (distance, start, end) = found?;
+    Some(FuzzyMatch {
+        score: MATCH_SCORE * pattern.len() as i64 - TYPO_PENALTY * distance as i64,
+        positions: (start..end).collect(),
+    })
+}
+
+// Damerau-Levenshtein (optimal string alignment) distance, None once it must exceed `max`
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in rows[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+        if rows[i].iter().all(|&distance| distance > max) {
+            return None;
+        }
+    }
+
+    Some(rows[a.len()][b.len()]).filter(|&distance| distance <= max)
+}
+
+// Registers `fuzzy(pattern, text...)` on the connection, the best score over the texts or NULL when none match
+pub fn register(conn: &Connection) -> Result<(), String> {
+    // SAFETY: the connection handle is valid for the call, the name is a NUL terminated literal and
+    // `fuzzy_sql` matches the signature SQLite expects for a scalar function taking any arguments
+    let result = unsafe {
+        ffi::sqlite3_create_function_v2(
+            conn.as_raw(),
+            c"fuzzy".as_ptr(),
+            -1,
+            ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+            std::ptr::null_mut(),
+            Some(fuzzy_sql),
+            None,
+            None,
+            None,
+        )
+    };
+    if result != ffi::SQLITE_OK {
+        return Err(format!(
+            "could not register the fuzzy function, SQLite error {}",
+            result
+        ));
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn fuzzy_sql(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: each value comes from `argv`, which SQLite keeps valid for the call. The text it
+    // returns stays valid until the value is converted again or freed, neither of which happens
+    // while it is borrowed here
+    let text = |value: *mut ffi::sqlite3_value| {
+        let text = ffi::sqlite3_value_text(value);
+        if text.is_null() {
+            None
+        } else {
+            CStr::from_ptr(text as *const c_char).to_str().ok()
+        }
+    };
+
+    // SAFETY: SQLite passes `argc` valid value pointers in `argv`
+    let args = std::slice::from_raw_parts(argv, argc.max(0) as usize);
+    let score = args.split_first().and_then(|(pattern, texts)| {
+        let pattern = text(*pattern)?;
+        texts
+            .iter()
+            .filter_map(|value| text(*value).and_then(|text| fuzzy_match(pattern, text)))
+            .map(|found| found.score)
+            .max()
+    });
+
+    // SAFETY: `context` is the one SQLite passed in and is valid until the function returns
+    match score {
+        Some(score) => ffi::sqlite3_result_int64(context, score),
+        None => ffi::sqlite3_result_null(context),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(pattern: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, text).map(|found| found.positions)
+    }
+
+    #[test]
+    fn matches_characters_in_order() {
+        assert_eq!(matched("chkn", "Chicken"), Some(vec![0, 1, 4, 6]));
+        assert_eq!(matched("milk", "Oat Milk"), Some(vec![4, 5, 6, 7]));
+        assert_eq!(matched("xyz", "Chicken"), None);
+    }
+
+    #[test]
+    fn prefers_word_starts_and_consecutive_matches() {
+        let chicken = fuzzy_match("chkn", "Chicken").unwrap().score;
+        let snack = fuzzy_match("chkn", "Snack Nuts ch").map_or(i64::MIN, |found| found.score);
+        assert!(chicken > snack);
+        assert!(
+            fuzzy_match("milk", "Milk").unwrap().score
+                > fuzzy_match("milk", "M i l k").unwrap().score
+        );
+    }
+
+    #[test]
+    fn matches_words_with_typos() {
+        assert_eq!(
+            matched("chikcen", "Chicken Breasts"),
+            Some((0..7).collect())
+        );
+        assert_eq!(
+            matched("chiken", "Chicken Breasts"),
+            Some(vec![0, 1, 2, 4, 5, 6])
+        );
+        assert_eq!(
+            matched("chocken", "Chicken Breasts"),
+            Some((0..7).collect())
+        );
+        assert_eq!(
+            matched("braest", "Chicken Breasts"),
+            Some((8..14).collect())
+        );
+        assert!(matched("chikcen", "Rice").is_none());
+    }
+
+    #[test]
+    fn ranks_typos_below_exact_matches() {
+        let exact = fuzzy_match("chicken", "Chicken").unwrap().score;
+        let typo = fuzzy_match("chikcen", "Chicken").unwrap().score;
+        assert!(exact > typo);
+    }
+
+    #[test]
+    fn short_patterns_need_no_typos() {
+        assert!(matched("mlk", "Milk").is_some());
+        assert!(matched("mikl", "Milk").is_some());
+        assert!(matched("xq", "Milk").is_none());
+    }
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one() {
+        let chars = |text: &str| text.chars().collect::<Vec<_>>();
+        assert_eq!(
+            edit_distance(&chars("chikcen"), &chars("chicken"), 2),
+            Some(1)
+        );
+        assert_eq!(
+            edit_distance(&chars("chiken"), &chars("chicken"), 2),
+            Some(1)
+        );
+        assert_eq!(edit_distance(&chars("bread"), &chars("chicken"), 2), None);
+    }
+
+    #[test]
+    fn sql_function_returns_the_best_score_or_null() {
+        let conn = sqlite::open(":memory:").unwrap();
+        register(&conn).unwrap();
+        let mut statement = conn
+            .prepare("SELECT fuzzy('milk', 'Oat Milk', 'Milk'), fuzzy('xyz', 'Milk', NULL)")
+            .unwrap();
+        statement.next().unwrap();
+        let best = [
+            fuzzy_match("milk", "Oat Milk").unwrap().score,
+            fuzzy_match("milk", "Milk").unwrap().score,
+        ];
+        assert_eq!(
+            statement.read::<Option<i64>, _>(0).unwrap(),
+            best.into_iter().max()
+        );
+        assert_eq!(statement.read::<Option<i64>, _>(1).unwrap(), None);
+    }
+}
@@ -0,0 +1,257 @@
+use std::error::Error;
+
+use chrono::{Datelike, NaiveDate};
+use sqlite::{Connection, State};
+
+use crate::app::{App, NewPurchase};
+use crate::db;
+use crate::money::Money;
+
+/// Earliest year a detected date may have, chrono's "%Y" also reads two digit years like "24" as 0024
+const MIN_YEAR: i32 = 1900;
+
+/// Date formats tried, in order, when detecting the format of a date column
+const DATE_FORMATS: [&str; 10] = [
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%m/%d/%Y",
+    "%d/%m/%Y",
+    "%m/%d/%y",
+    "%d/%m/%y",
+    "%d.%m.%Y",
+    "%m-%d-%Y",
+    "%b %d, %Y",
+    "%d %b %Y",
+];
+
+/// Source columns (header name or 1-based index) for each purchase field
+pub struct ColumnMapping {
+    pub ingredient: String,
+    pub price: String,
+    pub purchase_date: String,
+    pub expended_date: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct ImportRow {
+    pub ingredient: String,
+    pub price: Money,
+    pub purchase_date: String,
+    pub expended_date: String,
+    /// Row exactly matches a transaction already in the database or an earlier row of the file
+    pub duplicate: bool,
+    /// Refund or credit, signed opposite to the file's purchases
+    pub credit: bool,
+}
+
+impl ImportRow {
+    // Duplicates and credits are shown in the preview but not imported
+    pub fn skipped(&self) -> bool {
+        self.duplicate || self.credit
+    }
+
+    fn same_purchase(&self, other: &ImportRow) -> bool {
+        self.ingredient == other.ingredient
+            && self.price == other.price
+            && self.purchase_date == other.purchase_date
+            && self.expended_date == other.expended_date
+    }
+}
+
+fn column_index(headers: &csv::StringRecord, column: &str) -> Result<usize, Box<dyn Error>> {
+    if let Some(index) = headers
+        .iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(column))
+    {
+        return Ok(index);
+    }
+    match column.parse::<usize>() {
+        Ok(index) if index >= 1 && index <= headers.len() => Ok(index - 1),
+        _ => Err(format!("column \"{}\" not found in CSV header", column).into()),
+    }
+}
+
+fn parse_date(value: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, format)
+        .ok()
+        .filter(|date| date.year() >= MIN_YEAR)
+}
+
+// Picks the first format that parses every non-empty value in the column
+fn detect_date_format<'a>(values: impl Iterator<Item = &'a str> + Clone) -> Option<&'static str> {
+    DATE_FORMATS.into_iter().find(|format| {
+        values
+            .clone()
+            .filter(|value| !value.is_empty())
+            .all(|value| parse_date(value, format).is_some())
+    })
+}
+
+fn normalize_date(value: &str, format: Option<&str>) -> Result<String, Box<dyn Error>> {
+    match format.and_then(|format| parse_date(value, format)) {
+        Some(date) => Ok(date.format("%Y-%m-%d").to_string()),
+        None => Err(format!("unrecognized date \"{}\"", value).into()),
+    }
+}
+
+// Some bank exports list purchases as negative amounts and credits as positive ones, others the
+// reverse, so the sign most amounts share is taken to be the purchases'
+fn purchases_are_negative(amounts: &[Money]) -> bool {
+    let negative = amounts
+        .iter()
+        .filter(|amount| **amount < Money::ZERO)
+        .count();
+    negative > amounts.len() - negative
+}
+
+fn is_duplicate(conn: &Connection, row: &ImportRow) -> bool {
+    let query = "SELECT COUNT(*) AS matches FROM purchase WHERE ingredient = ? AND price = ? AND purchaseDate = ? AND expendedDate = ?";
+    let mut statement = conn.prepare(query).unwrap();
+
+    statement.bind((1, row.ingredient.as_str())).unwrap();
+    statement.bind((2, row.price.cents())).unwrap();
+    statement.bind((3, row.purchase_date.as_str())).unwrap();
+    statement.bind((4, row.expended_date.as_str())).unwrap();
+
+    match statement.next().unwrap() {
+        State::Row => statement.read::<i64, _>("matches").unwrap_or(0) > 0,
+        State::Done => false,
+    }
+}
+
+// Reads a CSV file into purchase rows using the given column mapping
+pub fn read_csv(path: &str, mapping: &ColumnMapping) -> Result<Vec<ImportRow>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let records = reader.records().collect::<Result<Vec<_>, _>>()?;
+
+    let ingredient_column = column_index(&headers, &mapping.ingredient)?;
+    let price_column = column_index(&headers, &mapping.price)?;
+    let purchase_column = column_index(&headers, &mapping.purchase_date)?;
+    let expended_column = match &mapping.expended_date {
+        Some(column) => Some(column_index(&headers, column)?),
+        None => None,
+    };
+
+    let field = |record: &csv::StringRecord, column: usize| {
+        record.get(column).unwrap_or("").trim().to_string()
+    };
+    let records: Vec<&csv::StringRecord> = records
+        .iter()
+        .filter(|record| !field(record, ingredient_column).is_empty())
+        .collect();
+
+    let purchase_values: Vec<String> = records
+        .iter()
+        .map(|record| field(record, purchase_column))
+        .collect();
+    let purchase_format = detect_date_format(purchase_values.iter().map(String::as_str));
+    let expended_values: Vec<String> = match expended_column {
+        Some(column) => records.iter().map(|record| field(record, column)).collect(),
+        None => vec![String::new(); records.len()],
+    };
+    let expended_format = detect_date_format(expended_values.iter().map(String::as_str));
+
+    let amounts = records
+        .iter()
+        .map(|record| Money::parse(&field(record, price_column)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let negative_purchases = purchases_are_negative(&amounts);
+
+    let conn = db::open();
+    let mut rows = Vec::<ImportRow>::new();
+    for (i, record) in records.iter().enumerate() {
+        let expended_date = if expended_values[i].is_empty() {
+            "NULL".to_string()
+        } else {
+            normalize_date(&expended_values[i], expended_format)?
+        };
+
+        let mut row = ImportRow {
+            ingredient: field(record, ingredient_column),
+            price: amounts[i].abs(),
+            purchase_date: normalize_date(&purchase_values[i], purchase_format)?,
+            expended_date,
+            duplicate: false,
+            credit: amounts[i] != Money::ZERO && (amounts[i] < Money::ZERO) != negative_purchases,
+        };
+        row.duplicate =
+            rows.iter().any(|staged| staged.same_purchase(&row)) || is_duplicate(&conn, &row);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+// Inserts the rows that are not skipped, returning how many were added
+pub fn commit(rows: &[ImportRow]) -> usize {
+    let mut count = 0;
+
+    for row in rows.iter().filter(|row| !row.skipped()) {
+        App::insert_purchase(&NewPurchase::new(
+            &row.ingredient,
+            row.price,
+            &row.purchase_date,
+            &row.expended_date,
+        ));
+        count += 1;
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(values: &[&str]) -> Option<&'static str> {
+        detect_date_format(values.iter().copied())
+    }
+
+    #[test]
+    fn detects_iso_dates() {
+        assert_eq!(detect(&["2024-10-05", "2024-11-30"]), Some("%Y-%m-%d"));
+    }
+
+    #[test]
+    fn detects_two_digit_years_as_this_century() {
+        let format = detect(&["10/05/24", "12/31/24"]);
+        assert_eq!(format, Some("%m/%d/%y"));
+        assert_eq!(normalize_date("10/05/24", format).unwrap(), "2024-10-05");
+    }
+
+    #[test]
+    fn detects_four_digit_years_before_two_digit_ones() {
+        assert_eq!(detect(&["10/05/2024"]), Some("%m/%d/%Y"));
+    }
+
+    #[test]
+    fn detects_day_first_dates_when_month_first_fails() {
+        assert_eq!(detect(&["05/10/2024", "31/12/2024"]), Some("%d/%m/%Y"));
+        assert_eq!(detect(&["31/12/24"]), Some("%d/%m/%y"));
+    }
+
+    #[test]
+    fn ignores_empty_values() {
+        assert_eq!(detect(&["", "2024-10-05"]), Some("%Y-%m-%d"));
+    }
+
+    #[test]
+    fn rejects_unknown_dates() {
+        assert_eq!(detect(&["yesterday"]), None);
+        assert!(normalize_date("yesterday", None).is_err());
+    }
+
+    #[test]
+    fn takes_the_majority_sign_as_purchases() {
+        let cents = |amounts: &[i64]| {
+            amounts
+                .iter()
+                .map(|cents| Money::from_cents(*cents))
+                .collect::<Vec<_>>()
+        };
+        assert!(purchases_are_negative(&cents(&[-499, -120, 300])));
+        assert!(!purchases_are_negative(&cents(&[499, 120, -300])));
+        assert!(!purchases_are_negative(&[]));
+    }
+}
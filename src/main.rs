@@ -1,22 +1,43 @@
-use std::{error::Error, io};
-use chrono::{Duration, Local};
+use chrono::Local;
+use clap::Parser;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     },
     Terminal,
 };
+use std::{error::Error, fs::File, io};
 
 mod app;
+mod audit;
+mod backup;
+mod category;
+mod cli;
+mod column;
+mod currency;
+mod db;
+mod export;
+mod fuzzy;
+mod import;
+mod money;
+mod pricing;
+mod query;
+mod report;
+mod settle;
+mod sort;
 mod ui;
+mod view;
+use crate::app::ItemInfo::Ingredient;
+use crate::column::Column;
+use crate::money::Money;
+use crate::sort::{Direction, Sort};
 use crate::{
-    app::{App, CurrentScreen, ItemInfo},
+    app::{resolve_date_input, App, CurrentScreen, ItemInfo, ShoppingItem},
     ui::ui,
 };
-use crate::app::ItemInfo::Ingredient;
 
 // fn main() -> color_eyre::Result<()> {
 //     color_eyre::install()?;
@@ -27,6 +48,36 @@ use crate::app::ItemInfo::Ingredient;
 // }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = cli::Cli::parse();
+    match &cli.profile {
+        Some(name) if !db::is_valid_profile(name) => {
+            return Err(format!(
+                "invalid profile name \"{}\", use letters, digits, '-' and '_'",
+                name
+            )
+            .into());
+        }
+        Some(name) => db::set_profile(name),
+        None => db::init(),
+    }
+
+    let mut app = App::new();
+    if let Some(command) = cli.command {
+        if command.mutates() {
+            backup::create_and_rotate()?;
+        }
+        if !cli::run(command, &mut app)? {
+            return Ok(());
+        }
+    } else {
+        backup::create_and_rotate()?;
+
+        app.due_templates = App::get_due_templates();
+        if !app.due_templates.is_empty() {
+            app.current_screen = CurrentScreen::RecurringConfirm;
+        }
+    }
+
     // setup terminal
     enable_raw_mode()?;
     let mut stderr = io::stderr(); // This is a special case. Normally using stdout is fine
@@ -34,8 +85,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stderr);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let mut app = App::new();
+    // run the app
     let _res = run_app(&mut terminal, &mut app);
 
     // restore terminal
@@ -62,71 +112,188 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
 
             match app.current_screen {
                 CurrentScreen::Main => match key.code {
-                        KeyCode::Char('q') => {
-                            return Ok(false);
-                        }
-                        KeyCode::Char('i') => {
-                            app.current_screen = CurrentScreen::SingleInput;
-                            app.currently_editing = Some(ItemInfo::PurchaseDate);
-                        }
-                        KeyCode::Down => {
-                            App::next(app);
-                        }
-                        KeyCode::Up => {
-                            App::prev(app);
-                        }
-                        KeyCode::Char('e') => {
-                            app.current_screen = CurrentScreen::EditExpended;
-                            app.currently_editing = Some(ItemInfo::ExpendedDate);
-                        }
-                        KeyCode::Char('E') => {
-                            let item_id = (&app.row_data[app.state.selected().ok_or(0).unwrap()][0]).clone();
+                    KeyCode::Char('q') => {
+                        return Ok(false);
+                    }
+                    KeyCode::Char('i') => {
+                        app.current_screen = CurrentScreen::SingleInput;
+                        app.currently_editing = Some(ItemInfo::PurchaseDate);
+                    }
+                    KeyCode::Down => {
+                        App::next(app);
+                    }
+                    KeyCode::Up => {
+                        App::prev(app);
+                    }
+                    KeyCode::PageDown => {
+                        app.page_down();
+                    }
+                    KeyCode::PageUp => {
+                        app.page_up();
+                    }
+                    KeyCode::Home => {
+                        app.select_index(0);
+                    }
+                    KeyCode::End => {
+                        app.select_index(app.item_count.saturating_sub(1));
+                    }
+                    KeyCode::Char('w') => {
+                        App::record_swipes(1, &resolve_date_input("t"));
+                        app.status_message = "Recorded a meal swipe".to_string();
+                    }
+                    KeyCode::Char('e') => {
+                        app.current_screen = CurrentScreen::EditExpended;
+                        app.currently_editing = Some(ItemInfo::ExpendedDate);
+                    }
+                    KeyCode::Char('E') => {
+                        if let Some(row) = app.selected_row() {
+                            let item_id = row.id;
                             let today = Local::now();
-                            let expended = format!("{}",today.format("%Y-%m-%d"));
+                            let expended = format!("{}", today.format("%Y-%m-%d"));
                             App::update_expended(item_id, expended);
+                            app.refresh_rows();
                         }
-                        KeyCode::Char('p') => {
-                            app.order_by = "price DESC".to_string();
-                        }
-                        KeyCode::Char('P') => {
-                            app.order_by = "price ASC".to_string();
-                        }
-                        KeyCode::Char('d') => {
-                            app.order_by = "purchaseDate DESC".to_string();
-                        }
-                        KeyCode::Char('D') => {
-                            app.order_by = "purchaseDate ASC".to_string();
-                        }
-                        KeyCode::Char('s') => {
-                            app.order_by = "expendedDate DESC, purchaseDate ASC".to_string();
+                    }
+                    KeyCode::Char('p') => {
+                        app.sort = Sort::by(Column::Price, Direction::Desc);
+                    }
+                    KeyCode::Char('P') => {
+                        app.sort = Sort::by(Column::Price, Direction::Asc);
+                    }
+                    KeyCode::Char('d') => {
+                        app.sort = Sort::by(Column::PurchaseDate, Direction::Desc);
+                    }
+                    KeyCode::Char('D') => {
+                        app.sort = Sort::by(Column::PurchaseDate, Direction::Asc);
+                    }
+                    KeyCode::Char('s') => {
+                        app.sort = Sort::default();
+                    }
+                    // Column number cycles sorting by that column, with Alt it is added as a further sort key
+                    KeyCode::Char(val @ '1'..='9') => {
+                        if let Some(setting) =
+                            app.columns.visible().get(val as usize - '1' as usize)
+                        {
+                            if key.modifiers.contains(KeyModifiers::ALT) {
+                                app.sort.cycle_key(setting.column);
+                            } else {
+                                app.sort.cycle(setting.column);
+                            }
                         }
-                        KeyCode::Char('Q') => {
-                            app.current_screen = CurrentScreen::Query;
+                    }
+                    KeyCode::Char('C') => {
+                        app.current_screen = CurrentScreen::Columns;
+                    }
+                    KeyCode::Char('Q') => {
+                        app.current_screen = CurrentScreen::Query;
+                    }
+                    KeyCode::Backspace => {
+                        app.query_input.clear();
+                        app.apply_query();
+                    }
+                    KeyCode::Char('a') => {
+                        app.current_screen = CurrentScreen::Analysis;
+                    }
+                    KeyCode::Char('b') => {
+                        app.current_screen = CurrentScreen::DiningInput;
+                        app.currently_editing = Some(ItemInfo::Location);
+                    }
+                    KeyCode::Char('B') => {
+                        app.current_screen = CurrentScreen::DiningBalance;
+                        app.currently_editing = Some(ItemInfo::Amount);
+                    }
+                    KeyCode::Char('t') => {
+                        app.templates = App::get_templates();
+                        app.current_screen = CurrentScreen::Templates;
+                    }
+                    KeyCode::Char('x') => {
+                        let path = export::default_export_path("csv");
+                        let result = export::read_rows(app).and_then(|rows| {
+                            export::write_csv(&rows, File::create(&path)?)?;
+                            Ok(rows.len())
+                        });
+                        app.status_message = match result {
+                            Ok(count) => format!("Exported {} rows to {}", count, path),
+                            Err(err) => format!("Export failed: {}", err),
+                        };
+                    }
+                    KeyCode::Char('h') => {
+                        let item_id = app.selected_row().map(|row| row.id);
+                        if item_id.is_some() {
+                            app.audit_entries = audit::entries(item_id);
+                            app.current_screen = CurrentScreen::AuditLog;
                         }
-                        KeyCode::Backspace => {
-                            app.search_param = "true".to_string();
-                            app.query_input = "".to_string();
+                    }
+                    KeyCode::Char('H') => {
+                        app.audit_entries = audit::entries(None);
+                        app.current_screen = CurrentScreen::AuditLog;
+                    }
+                    KeyCode::Char('u') => {
+                        app.current_screen = CurrentScreen::SettleUp;
+                    }
+                    KeyCode::Char('c') => {
+                        app.current_screen = CurrentScreen::StorePrices;
+                    }
+                    KeyCode::Char('v') => {
+                        app.views = view::all();
+                        app.current_screen = CurrentScreen::Views;
+                    }
+                    KeyCode::Char('o') => {
+                        app.profiles = db::profiles();
+                        app.current_screen = CurrentScreen::Profiles;
+                    }
+                    KeyCode::Char('l') => {
+                        if app.shopping_list.is_empty() {
+                            app.shopping_list = App::get_shopping_suggestions();
                         }
-                        _ => {}
-                }
+                        app.current_screen = CurrentScreen::ShoppingList;
+                    }
+                    _ => {}
+                },
                 CurrentScreen::SingleInput => match key.code {
                     KeyCode::Tab => match app.currently_editing {
                         Some(ItemInfo::Ingredient) => {
                             app.currently_editing = Some(ItemInfo::Price);
                         }
                         Some(ItemInfo::Price) => {
+                            app.currently_editing = Some(ItemInfo::OriginalPrice);
+                        }
+                        Some(ItemInfo::OriginalPrice) => {
+                            app.currently_editing = Some(ItemInfo::Discount);
+                        }
+                        Some(ItemInfo::Discount) => {
+                            app.currently_editing = Some(ItemInfo::TaxRate);
+                        }
+                        Some(ItemInfo::TaxRate) => {
+                            app.currently_editing = Some(ItemInfo::Currency);
+                        }
+                        Some(ItemInfo::Currency) => {
+                            app.currently_editing = Some(ItemInfo::Store);
+                        }
+                        Some(ItemInfo::Store) => {
                             app.currently_editing = Some(ItemInfo::ExpendedDate);
                         }
                         Some(ItemInfo::ExpendedDate) => {
+                            app.currently_editing = Some(ItemInfo::PaidBy);
+                        }
+                        Some(ItemInfo::PaidBy) => {
+                            app.currently_editing = Some(ItemInfo::SplitBetween);
+                        }
+                        Some(ItemInfo::SplitBetween) => {
                             app.currently_editing = Some(ItemInfo::Ingredient);
                         }
                         _ => {}
-                    }
+                    },
 
                     KeyCode::Esc => {
                         app.current_screen = CurrentScreen::Main;
                         app.currently_editing = None;
                     }
+                    KeyCode::Right if matches!(app.currently_editing, Some(ItemInfo::Store)) => {
+                        if let Some(store) = app.store_suggestion() {
+                            app.store_input = store;
+                        }
+                    }
                     KeyCode::Backspace => {
                         if let Some(editing) = &app.currently_editing {
                             match editing {
@@ -136,12 +303,33 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                 ItemInfo::Price => {
                                     app.price_input.pop();
                                 }
+                                ItemInfo::OriginalPrice => {
+                                    app.original_price_input.pop();
+                                }
+                                ItemInfo::Discount => {
+                                    app.discount_input.pop();
+                                }
+                                ItemInfo::TaxRate => {
+                                    app.tax_rate_input.pop();
+                                }
+                                ItemInfo::Currency => {
+                                    app.currency_input.pop();
+                                }
+                                ItemInfo::Store => {
+                                    app.store_input.pop();
+                                }
                                 ItemInfo::ExpendedDate => {
                                     app.expended_date_input.pop();
                                 }
                                 ItemInfo::PurchaseDate => {
                                     app.purchase_date_input.pop();
                                 }
+                                ItemInfo::PaidBy => {
+                                    app.paid_by_input.pop();
+                                }
+                                ItemInfo::SplitBetween => {
+                                    app.split_between_input.pop();
+                                }
                                 _ => {}
                             }
                         }
@@ -155,12 +343,33 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                 ItemInfo::Price => {
                                     app.price_input.push(value);
                                 }
+                                ItemInfo::OriginalPrice => {
+                                    app.original_price_input.push(value);
+                                }
+                                ItemInfo::Discount => {
+                                    app.discount_input.push(value);
+                                }
+                                ItemInfo::TaxRate => {
+                                    app.tax_rate_input.push(value);
+                                }
+                                ItemInfo::Currency => {
+                                    app.currency_input.push(value);
+                                }
+                                ItemInfo::Store => {
+                                    app.store_input.push(value);
+                                }
                                 ItemInfo::ExpendedDate => {
                                     app.expended_date_input.push(value);
                                 }
                                 ItemInfo::PurchaseDate => {
                                     app.purchase_date_input.push(value);
                                 }
+                                ItemInfo::PaidBy => {
+                                    app.paid_by_input.push(value);
+                                }
+                                ItemInfo::SplitBetween => {
+                                    app.split_between_input.push(value);
+                                }
                                 _ => {}
                             }
                         }
@@ -174,22 +383,40 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                     }
                                 }
                                 _ => {
-                                    if !app.ingredient_input.is_empty() && ! app.price_input.is_empty() {
+                                    let price = Money::parse(&app.price_input);
+                                    let original_price =
+                                        pricing::parse_optional_amount(&app.original_price_input);
+                                    let discount =
+                                        pricing::parse_optional_amount(&app.discount_input);
+                                    let valid = !app.ingredient_input.is_empty()
+                                        && currency::for_purchase(&app.currency_input).is_ok()
+                                        && pricing::parse_tax_rate(&app.tax_rate_input).is_ok()
+                                        && match (price, original_price, discount) {
+                                            (Ok(price), Ok(original_price), Ok(discount)) => {
+                                                pricing::validate(price, original_price, discount)
+                                                    .is_ok()
+                                            }
+                                            _ => false,
+                                        };
+                                    if valid {
                                         App::submit_ingredient(app);
+                                        app.refresh_rows();
                                         app.current_screen = CurrentScreen::SingleInput;
                                         app.currently_editing = Some(Ingredient);
                                         app.ingredient_input.clear();
                                         app.price_input.clear();
+                                        app.original_price_input.clear();
+                                        app.discount_input.clear();
                                         app.expended_date_input.clear();
+                                        app.split_between_input.clear();
                                     }
                                 }
                             }
                         }
                         // implement submission logic
-
                     }
                     _ => {}
-                }
+                },
                 CurrentScreen::EditExpended => match key.code {
                     KeyCode::Backspace => {
                         app.expended_date_input.pop();
@@ -203,52 +430,472 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                         app.currently_editing = None;
                     }
                     KeyCode::Enter => {
-                        let item_id = (&app.row_data[app.state.selected().ok_or(0).unwrap()][0]).clone();
-                        let mut expended = app.expended_date_input.clone();
-
-                        match expended.to_lowercase().as_str() {
-                            "t" => {
-                                let today = Local::now();
-                                expended = format!("{}",today.format("%Y-%m-%d"));
-                            }
+                        if let Some(row) = app.selected_row() {
+                            let item_id = row.id;
+                            let expended = resolve_date_input(&app.expended_date_input);
 
-                            "y" => {
-                                let yesterday = Local::now() - Duration::days(1);
-                                expended = format!("{}", yesterday.format("%Y-%m-%d"));
-                            }
-                            _ => {}
+                            App::update_expended(item_id, expended);
+                            app.refresh_rows();
                         }
-
-                        App::update_expended(item_id, expended);
                         app.expended_date_input.clear();
                         app.current_screen = CurrentScreen::Main;
                         app.currently_editing = None;
                     }
                     _ => {}
-                }
+                },
                 CurrentScreen::Query => match key.code {
                     KeyCode::Esc => {
                         app.current_screen = CurrentScreen::Main;
-                        app.search_param = "true".to_string();
-                        app.query_input = "".to_string();
+                        app.query_input.clear();
+                        app.apply_query();
                     }
                     KeyCode::Char(val) => {
                         app.query_input.push(val);
-                        app.search_param = format!("ingredient LIKE \"%{}%\"", app.query_input.clone());
+                        app.apply_query();
                     }
                     KeyCode::Backspace => {
                         app.query_input.pop();
-                        app.search_param = format!("ingredient LIKE \"%{}%\"", app.query_input.clone());
+                        app.apply_query();
                     }
                     KeyCode::Enter => {
                         app.current_screen = CurrentScreen::Main;
                     }
-                    _ => {
-
+                    _ => {}
+                },
+                CurrentScreen::DiningInput => match key.code {
+                    KeyCode::Tab => match app.currently_editing {
+                        Some(ItemInfo::Location) => {
+                            app.currently_editing = Some(ItemInfo::Amount);
+                        }
+                        Some(ItemInfo::Amount) => {
+                            app.currently_editing = Some(ItemInfo::TransactionDate);
+                        }
+                        _ => {
+                            app.currently_editing = Some(ItemInfo::Location);
+                        }
+                    },
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Main;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Backspace => match app.currently_editing {
+                        Some(ItemInfo::Location) => {
+                            app.location_input.pop();
+                        }
+                        Some(ItemInfo::Amount) => {
+                            app.amount_input.pop();
+                        }
+                        Some(ItemInfo::TransactionDate) => {
+                            app.transaction_date_input.pop();
+                        }
+                        _ => {}
+                    },
+                    KeyCode::Char(value) => match app.currently_editing {
+                        Some(ItemInfo::Location) => {
+                            app.location_input.push(value);
+                        }
+                        Some(ItemInfo::Amount) => {
+                            app.amount_input.push(value);
+                        }
+                        Some(ItemInfo::TransactionDate) => {
+                            app.transaction_date_input.push(value);
+                        }
+                        _ => {}
+                    },
+                    KeyCode::Enter
+                        if !app.location_input.is_empty()
+                            && Money::parse(&app.amount_input).is_ok() =>
+                    {
+                        App::submit_dining_transaction(app);
+                        app.currently_editing = Some(ItemInfo::Location);
+                        app.location_input.clear();
+                        app.amount_input.clear();
+                        app.transaction_date_input.clear();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::DiningBalance => match key.code {
+                    KeyCode::Backspace => {
+                        app.dining_balance_input.pop();
+                    }
+                    KeyCode::Char(val) => {
+                        app.dining_balance_input.push(val);
+                    }
+                    KeyCode::Esc => {
+                        app.dining_balance_input.clear();
+                        app.current_screen = CurrentScreen::Main;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Enter if Money::parse(&app.dining_balance_input).is_ok() => {
+                        App::set_dining_balance(app);
+                        app.dining_balance_input.clear();
+                        app.current_screen = CurrentScreen::Main;
+                        app.currently_editing = None;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Templates => match key.code {
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Down => {
+                        app.template_state.select_next();
+                    }
+                    KeyCode::Up => {
+                        app.template_state.select_previous();
+                    }
+                    KeyCode::Char('n') => {
+                        app.current_screen = CurrentScreen::TemplateInput;
+                        app.currently_editing = Some(ItemInfo::TemplateName);
+                    }
+                    KeyCode::Char('x') => {
+                        if let Some(template) = app
+                            .template_state
+                            .selected()
+                            .and_then(|i| app.templates.get(i))
+                        {
+                            App::delete_template(&template.name);
+                            app.templates = App::get_templates();
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some(template) = app
+                            .template_state
+                            .selected()
+                            .and_then(|i| app.templates.get(i))
+                        {
+                            let skipped = App::apply_template(template);
+                            app.status_message = App::template_status(&template.name, &skipped);
+                            app.refresh_rows();
+                            app.current_screen = CurrentScreen::Main;
+                        }
+                    }
+                    _ => {}
+                },
+                CurrentScreen::TemplateInput => match key.code {
+                    KeyCode::Tab => match app.currently_editing {
+                        Some(ItemInfo::TemplateName) => {
+                            app.currently_editing = Some(ItemInfo::TemplateItems);
+                        }
+                        Some(ItemInfo::TemplateItems) => {
+                            app.currently_editing = Some(ItemInfo::TemplateEvery);
+                        }
+                        _ => {
+                            app.currently_editing = Some(ItemInfo::TemplateName);
+                        }
+                    },
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Templates;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Backspace => match app.currently_editing {
+                        Some(ItemInfo::TemplateName) => {
+                            app.template_name_input.pop();
+                        }
+                        Some(ItemInfo::TemplateItems) => {
+                            app.template_items_input.pop();
+                        }
+                        Some(ItemInfo::TemplateEvery) => {
+                            app.template_every_input.pop();
+                        }
+                        _ => {}
+                    },
+                    KeyCode::Char(value) => match app.currently_editing {
+                        Some(ItemInfo::TemplateName) => {
+                            app.template_name_input.push(value);
+                        }
+                        Some(ItemInfo::TemplateItems) => {
+                            app.template_items_input.push(value);
+                        }
+                        Some(ItemInfo::TemplateEvery) => {
+                            app.template_every_input.push(value);
+                        }
+                        _ => {}
+                    },
+                    KeyCode::Enter
+                        if !app.template_name_input.is_empty()
+                            && !app.template_items_input.is_empty() =>
+                    {
+                        App::submit_template(app);
+                        app.templates = App::get_templates();
+                        app.current_screen = CurrentScreen::Templates;
+                        app.currently_editing = None;
+                        app.template_name_input.clear();
+                        app.template_items_input.clear();
+                        app.template_every_input.clear();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::RecurringConfirm => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        let template = app.due_templates.remove(0);
+                        let skipped = App::apply_template(&template);
+                        app.status_message = App::template_status(&template.name, &skipped);
+                        app.refresh_rows();
+                    }
+                    KeyCode::Char('n') => {
+                        let template = app.due_templates.remove(0);
+                        App::mark_template_applied(&template.name);
+                    }
+                    KeyCode::Esc => {
+                        app.due_templates.clear();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ShoppingList => match key.code {
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Down => {
+                        app.shopping_state.select_next();
+                    }
+                    KeyCode::Up => {
+                        app.shopping_state.select_previous();
+                    }
+                    KeyCode::Char('n') => {
+                        app.current_screen = CurrentScreen::ShoppingInput;
+                        app.currently_editing = Some(ItemInfo::ShoppingItem);
+                    }
+                    KeyCode::Char('x') => {
+                        if let Some(i) = app
+                            .shopping_state
+                            .selected()
+                            .filter(|&i| i < app.shopping_list.len())
+                        {
+                            app.shopping_list.remove(i);
+                        }
+                    }
+                    KeyCode::Char('r') => {
+                        app.shopping_list = App::get_shopping_suggestions();
+                    }
+                    KeyCode::Char('c') => {
+                        App::convert_shopping_list(app);
+                        app.refresh_rows();
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ShoppingInput => match key.code {
+                    KeyCode::Esc => {
+                        app.shopping_input.clear();
+                        app.current_screen = CurrentScreen::ShoppingList;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Backspace => {
+                        app.shopping_input.pop();
+                    }
+                    KeyCode::Char(val) => {
+                        app.shopping_input.push(val);
+                    }
+                    KeyCode::Enter if !app.shopping_input.is_empty() => {
+                        app.shopping_list.push(ShoppingItem::new(
+                            &app.shopping_input,
+                            App::get_last_price(&app.shopping_input),
+                        ));
+                        app.shopping_input.clear();
+                        app.current_screen = CurrentScreen::ShoppingList;
+                        app.currently_editing = None;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ImportPreview => match key.code {
+                    KeyCode::Enter => {
+                        let count = import::commit(&app.import_rows);
+                        app.refresh_rows();
+                        app.status_message = format!(
+                            "Imported {} rows, skipped {} duplicates or credits",
+                            count,
+                            app.import_rows.len() - count
+                        );
+                        app.import_rows.clear();
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Esc => {
+                        app.status_message = "Import cancelled".to_string();
+                        app.import_rows.clear();
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Columns => {
+                    let selected = app.column_state.selected().unwrap_or(0);
+                    let changed = match key.code {
+                        KeyCode::Esc | KeyCode::Enter => {
+                            app.current_screen = CurrentScreen::Main;
+                            false
+                        }
+                        KeyCode::Down => {
+                            app.column_state.select_next();
+                            false
+                        }
+                        KeyCode::Up => {
+                            app.column_state.select_previous();
+                            false
+                        }
+                        KeyCode::Char(' ') => app.columns.toggle(selected),
+                        KeyCode::Char('+') | KeyCode::Right => app.columns.resize(selected, 1),
+                        KeyCode::Char('-') | KeyCode::Left => app.columns.resize(selected, -1),
+                        KeyCode::Char('K') | KeyCode::Char('J') => {
+                            let moved = app.columns.shift(selected, key.code == KeyCode::Char('K'));
+                            app.column_state.select(Some(moved));
+                            moved != selected
+                        }
+                        _ => false,
+                    };
+                    if changed {
+                        app.columns.save();
                     }
                 }
-                _ => {}
+                CurrentScreen::Views => match key.code {
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Down => {
+                        app.view_state.select_next();
+                    }
+                    KeyCode::Up => {
+                        app.view_state.select_previous();
+                    }
+                    KeyCode::Char('n') => {
+                        app.current_screen = CurrentScreen::ViewInput;
+                        app.currently_editing = Some(ItemInfo::ViewName);
+                    }
+                    KeyCode::Char('x') => {
+                        if let Some(view) = app.view_state.selected().and_then(|i| app.views.get(i))
+                        {
+                            view::delete(&view.name);
+                            app.views = view::all();
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some(view) = app
+                            .view_state
+                            .selected()
+                            .and_then(|i| app.views.get(i))
+                            .cloned()
+                        {
+                            app.apply_view(&view);
+                            app.current_screen = CurrentScreen::Main;
+                        }
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ViewInput => match key.code {
+                    KeyCode::Esc => {
+                        app.view_input.clear();
+                        app.current_screen = CurrentScreen::Views;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Backspace => {
+                        app.view_input.pop();
+                    }
+                    KeyCode::Char(value) => {
+                        app.view_input.push(value);
+                    }
+                    KeyCode::Enter if !app.view_input.trim().is_empty() => {
+                        app.save_view();
+                        app.current_screen = CurrentScreen::Views;
+                        app.currently_editing = None;
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Profiles => match key.code {
+                    KeyCode::Esc => {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Down => {
+                        app.profile_state.select_next();
+                    }
+                    KeyCode::Up => {
+                        app.profile_state.select_previous();
+                    }
+                    KeyCode::Char('n') => {
+                        app.current_screen = CurrentScreen::ProfileInput;
+                        app.currently_editing = Some(ItemInfo::ProfileName);
+                    }
+                    KeyCode::Enter => {
+                        if let Some(name) = app
+                            .profile_state
+                            .selected()
+                            .and_then(|i| app.profiles.get(i))
+                            .cloned()
+                        {
+                            app.switch_profile(&name);
+                        }
+                    }
+                    _ => {}
+                },
+                CurrentScreen::ProfileInput => match key.code {
+                    KeyCode::Esc => {
+                        app.profile_input.clear();
+                        app.current_screen = CurrentScreen::Profiles;
+                        app.currently_editing = None;
+                    }
+                    KeyCode::Backspace => {
+                        app.profile_input.pop();
+                    }
+                    KeyCode::Char(value)
+                        if value.is_ascii_alphanumeric() || value == '-' || value == '_' =>
+                    {
+                        app.profile_input.push(value);
+                    }
+                    KeyCode::Enter if db::is_valid_profile(&app.profile_input) => {
+                        let name = std::mem::take(&mut app.profile_input);
+                        app.currently_editing = None;
+                        app.switch_profile(&name);
+                    }
+                    _ => {}
+                },
+                CurrentScreen::StorePrices => {
+                    if key.code == KeyCode::Esc {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                }
+                CurrentScreen::SettleUp => {
+                    if key.code == KeyCode::Esc {
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                }
+                CurrentScreen::AuditLog => match key.code {
+                    KeyCode::Esc => {
+                        app.audit_entries.clear();
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Down => {
+                        app.audit_state.select_next();
+                    }
+                    KeyCode::Up => {
+                        app.audit_state.select_previous();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::Analysis => match key.code {
+                    KeyCode::Esc => {
+                        app.servings_input.clear();
+                        app.current_screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Char(val) if val.is_ascii_digit() => {
+                        app.servings_input.push(val);
+                    }
+                    KeyCode::Backspace => {
+                        app.servings_input.pop();
+                    }
+                    KeyCode::Enter => {
+                        if let Ok(servings) = app.servings_input.parse::<i64>() {
+                            App::record_meal(servings);
+                        }
+                        app.servings_input.clear();
+                    }
+                    _ => {}
+                },
+                CurrentScreen::RemoveConfirmation => {}
             };
+
+            if let CurrentScreen::RecurringConfirm = app.current_screen {
+                if app.due_templates.is_empty() {
+                    app.current_screen = CurrentScreen::Main;
+                }
+            }
         }
     }
 }
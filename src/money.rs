@@ -0,0 +1,438 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::sync::RwLock;
+
+use crate::db;
+
+/// Longest currency symbol, so amounts still fit their table columns
+const MAX_SYMBOL_LEN: usize = 3;
+
+/// How amounts are written, saved per profile in the setting table
+#[derive(Clone)]
+pub struct MoneyFormat {
+    pub symbol: String,
+    pub decimal_separator: char,
+    /// Groups thousands in the whole part, None to leave them ungrouped
+    pub thousands_separator: Option<char>,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> MoneyFormat {
+        MoneyFormat {
+            symbol: "$".to_string(),
+            decimal_separator: '.',
+            thousands_separator: Some(','),
+        }
+    }
+}
+
+impl MoneyFormat {
+    pub fn load() -> MoneyFormat {
+        let default = MoneyFormat::default();
+        let separator = |key: &str| db::get_setting(key).map(|value| value.chars().next());
+
+        MoneyFormat {
+            symbol: db::get_setting("currency_symbol").unwrap_or(default.symbol),
+            decimal_separator: separator("decimal_separator")
+                .flatten()
+                .unwrap_or(default.decimal_separator),
+            thousands_separator: separator("thousands_separator")
+                .unwrap_or(default.thousands_separator),
+        }
+    }
+
+    pub fn save(&self) {
+        db::set_setting("currency_symbol", &self.symbol);
+        db::set_setting("decimal_separator", &self.decimal_separator.to_string());
+        db::set_setting(
+            "thousands_separator",
+            &self
+                .thousands_separator
+                .map(String::from)
+                .unwrap_or_default(),
+        );
+    }
+
+    // Separators must tell amounts apart, so no digits, signs or two of the same. The symbol is
+    // short and has none of those or any separator, so amounts written with it parse again
+    pub fn validate(&self) -> Result<(), String> {
+        let bad = |c: char| c.is_ascii_digit() || c == '-' || c == '+';
+        let symbol_len = self.symbol.chars().count();
+        if symbol_len == 0 || symbol_len > MAX_SYMBOL_LEN {
+            return Err(format!(
+                "the symbol must be 1 to {} characters",
+                MAX_SYMBOL_LEN
+            ));
+        }
+        if self.symbol.chars().any(|c| {
+            bad(c)
+                || c.is_whitespace()
+                || c.is_control()
+                || c == '.'
+                || c == ','
+                || c == self.decimal_separator
+                || Some(c) == self.thousands_separator
+        }) {
+            return Err(
+                "the symbol cannot contain digits, signs, spaces or separators".to_string(),
+            );
+        }
+        if bad(self.decimal_separator) || self.thousands_separator.is_some_and(bad) {
+            return Err("separators cannot be digits or signs".to_string());
+        }
+        if self.thousands_separator == Some(self.decimal_separator) {
+            return Err("the decimal and thousands separators must differ".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Format used to display amounts, loaded for the active profile
+static FORMAT: RwLock<Option<MoneyFormat>> = RwLock::new(None);
+
+pub fn format() -> MoneyFormat {
+    FORMAT.read().unwrap().clone().unwrap_or_default()
+}
+
+pub fn set_format(format: MoneyFormat) {
+    *FORMAT.write().unwrap() = Some(format);
+}
+
+/// An amount of money in whole cents, so sums and splits stay exact
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub fn from_cents(cents: i64) -> Money {
+        Money(cents)
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    pub fn abs(self) -> Money {
+        Money(self.0.abs())
+    }
+
+    // Reads amounts such as "$4.99", "4,99", "4", "-3.50" and "1,234.56" or "1.234,56". The last
+    // "." or "," is the decimal point when one or two digits follow it, otherwise it groups thousands.
+    // Separators must sit between digits, grouping thousands in threes, and at most one sign leads.
+    // The symbol may come before or after the number, and spaces only next to the symbol or sign
+    pub fn parse(input: &str) -> Result<Money, String> {
+        let format = format();
+        let invalid = || format!("invalid amount \"{}\"", input);
+        let symbols = [format.symbol.as_str(), "$"];
+
+        let mut text = input.trim();
+        let mut sign = None;
+        let mut take_sign = |text: &mut &str| {
+            if sign.is_none() {
+                if let Some(rest) = text.strip_prefix(['-', '+']) {
+                    sign = text.chars().next();
+                    *text = rest.trim_start();
+                }
+            }
+        };
+
+        take_sign(&mut text);
+        let prefixed = symbols.iter().find_map(|symbol| text.strip_prefix(symbol));
+        if let Some(rest) = prefixed {
+            text = rest.trim_start();
+            take_sign(&mut text);
+        } else if let Some(rest) = symbols.iter().find_map(|symbol| text.strip_suffix(symbol)) {
+            text = rest.trim_end();
+        }
+        let negative = sign == Some('-');
+
+        let is_separator = |c: char| {
+            c == '.'
+                || c == ','
+                || c == format.decimal_separator
+                || Some(c) == format.thousands_separator
+        };
+        if !text.chars().any(|c| c.is_ascii_digit())
+            || !text.chars().all(|c| c.is_ascii_digit() || is_separator(c))
+        {
+            return Err(invalid());
+        }
+
+        let (whole, fraction) = match text.rfind(|c: char| !c.is_ascii_digit()) {
+            Some(i) => {
+                let separator = text[i..].chars().next().unwrap();
+                let after = &text[i + separator.len_utf8()..];
+                match after.len() {
+                    1..=2 => (&text[..i], after),
+                    3 if separator != format.decimal_separator => (text, ""),
+                    0 => return Err(invalid()),
+                    _ => return Err(format!("{}, at most two decimal places", invalid())),
+                }
+            }
+            None => (text, ""),
+        };
+
+        // Whatever is left of the decimal point is digits, optionally grouped by one separator
+        let groups: Vec<&str> = whole.split(|c: char| !c.is_ascii_digit()).collect();
+        let mut separators = whole.chars().filter(|c| !c.is_ascii_digit());
+        let first_separator = separators.next();
+        if groups.len() > 1 {
+            let grouped = separators.all(|c| Some(c) == first_separator)
+                && (1..=3).contains(&groups[0].len())
+                && groups[1..].iter().all(|group| group.len() == 3);
+            if !grouped {
+                return Err(invalid());
+            }
+        }
+
+        let whole: String = groups.concat();
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse::<i64>().map_err(|_| invalid())?
+        };
+        let fraction = format!("{:0<2}", fraction)
+            .parse::<i64>()
+            .map_err(|_| invalid())?;
+        let cents = whole
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(fraction))
+            .ok_or_else(invalid)?;
+
+        Ok(Money(if negative { -cents } else { cents }))
+    }
+
+    // Amount without symbol or grouping, e.g. "-1234.56", for files and settings read back by programs
+    pub fn plain(self) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        format!(
+            "{}{}.{:02}",
+            sign,
+            self.0.unsigned_abs() / 100,
+            self.0.unsigned_abs() % 100
+        )
+    }
+
+    // Amount followed by a currency code instead of the symbol, e.g. "4.99 EUR"
+    pub fn in_currency(self, code: &str) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        format!("{}{} {}", sign, self.digits(&format()), code)
+    }
+
+    // Unsigned amount with the format's separators
+    fn digits(self, format: &MoneyFormat) -> String {
+        let whole = (self.0.unsigned_abs() / 100).to_string();
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.extend(format.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+
+        format!(
+            "{}{}{:02}",
+            grouped,
+            format.decimal_separator,
+            self.0.unsigned_abs() % 100
+        )
+    }
+
+    // Splits into `parts` shares that add up exactly, the leftover cents going to the first shares
+    pub fn split(self, parts: usize) -> Vec<Money> {
+        let parts = parts.max(1) as i64;
+        let share = self.0.div_euclid(parts);
+        let remainder = self.0.rem_euclid(parts);
+
+        (0..parts)
+            .map(|i| Money(share + if i < remainder { 1 } else { 0 }))
+            .collect()
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let format = format();
+        let sign = if self.0 < 0 { "-" } else { "" };
+
+        f.pad(&format!(
+            "{}{}{}",
+            sign,
+            format.symbol,
+            self.digits(&format)
+        ))
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Mul<i64> for Money {
+    type Output = Money;
+
+    fn mul(self, times: i64) -> Money {
+        Money(self.0 * times)
+    }
+}
+
+// Rounds to the nearest cent, halves away from zero
+impl Div<i64> for Money {
+    type Output = Money;
+
+    fn div(self, divisor: i64) -> Money {
+        let quotient = self.0 / divisor;
+        let remainder = self.0 % divisor;
+        if remainder.abs() * 2 >= divisor.abs() {
+            Money(quotient + if (self.0 < 0) == (divisor < 0) { 1 } else { -1 })
+        } else {
+            Money(quotient)
+        }
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|money| money.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(input: &str) -> Result<i64, String> {
+        Money::parse(input).map(Money::cents)
+    }
+
+    #[test]
+    fn parses_common_amounts() {
+        assert_eq!(cents("$4.99"), Ok(499));
+        assert_eq!(cents("4,99"), Ok(499));
+        assert_eq!(cents("4"), Ok(400));
+        assert_eq!(cents("4.5"), Ok(450));
+        assert_eq!(cents(".99"), Ok(99));
+        assert_eq!(cents(" -3.50 "), Ok(-350));
+        assert_eq!(cents("+3.50"), Ok(350));
+        assert_eq!(cents("- $ 3.50"), Ok(-350));
+        assert_eq!(cents("$-3.50"), Ok(-350));
+        assert_eq!(cents("4.99$"), Ok(499));
+        assert_eq!(cents("4.99 $"), Ok(499));
+    }
+
+    #[test]
+    fn parses_thousands_groups() {
+        assert_eq!(cents("1,234.56"), Ok(123456));
+        assert_eq!(cents("1.234,56"), Ok(123456));
+        assert_eq!(cents("1,234"), Ok(123400));
+        assert_eq!(cents("1,234,567"), Ok(123456700));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for input in [
+            "4 99",
+            "4$99",
+            "$4.99$",
+            "- 4 .99",
+            "$$4",
+            "4.99 $ 1",
+            "1.2.3",
+            "1,,,2",
+            ".",
+            ",",
+            "--5",
+            "-+5",
+            "",
+            "$",
+            "abc",
+            "4.",
+            "1,23,456",
+            "12,34.56",
+            "1.234.5678",
+            "1,234.567",
+            "1.234,567.89",
+        ] {
+            assert!(Money::parse(input).is_err(), "{:?} should not parse", input);
+        }
+    }
+
+    #[test]
+    fn rejects_three_decimals_after_the_decimal_separator() {
+        assert!(Money::parse("1.234")
+            .unwrap_err()
+            .contains("at most two decimal places"));
+    }
+
+    #[test]
+    fn formats_with_symbol_and_grouping() {
+        assert_eq!(Money::from_cents(123456).to_string(), "$1,234.56");
+        assert_eq!(Money::from_cents(-5).to_string(), "-$0.05");
+        assert_eq!(Money::from_cents(-123456).plain(), "-1234.56");
+        assert_eq!(Money::from_cents(499).in_currency("EUR"), "4.99 EUR");
+    }
+
+    #[test]
+    fn splits_and_divides_exactly() {
+        let shares = Money::from_cents(100).split(3);
+        assert_eq!(
+            shares,
+            [
+                Money::from_cents(34),
+                Money::from_cents(33),
+                Money::from_cents(33)
+            ]
+        );
+        assert_eq!(Money::from_cents(5) / 2, Money::from_cents(3));
+        assert_eq!(Money::from_cents(-5) / 2, Money::from_cents(-3));
+    }
+
+    #[test]
+    fn validates_the_symbol() {
+        let with_symbol = |symbol: &str| MoneyFormat {
+            symbol: symbol.to_string(),
+            ..MoneyFormat::default()
+        };
+        for symbol in ["$", "€", "kr", "CHF"] {
+            assert!(with_symbol(symbol).validate().is_ok(), "{:?}", symbol);
+        }
+        for symbol in ["", "EURO", "$1", "k r", "-", "$.", "a,"] {
+            assert!(with_symbol(symbol).validate().is_err(), "{:?}", symbol);
+        }
+    }
+}
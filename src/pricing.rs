@@ -0,0 +1,187 @@
+use crate::money::Money;
+
+/// SQL for what was actually paid for a purchase, in its own currency: the price less any
+/// discount or coupon, plus tax. Rounded the same way as `net_price`
+pub const NET_PRICE_SQL: &str = "CAST(round((purchase.price - coalesce(purchase.discount, 0)) * (1 + coalesce(purchase.taxRate, 0) / 100.0)) AS INTEGER)";
+
+/// SQL for what a purchase saved against its original shelf price, counting sales and coupons
+pub const SAVINGS_SQL: &str = "(max(coalesce(purchase.originalPrice, purchase.price) - purchase.price, 0) + coalesce(purchase.discount, 0))";
+
+pub fn net_price(price: Money, discount: Option<Money>, tax_rate: Option<f64>) -> Money {
+    let before_tax = price - discount.unwrap_or_default();
+    let cents = before_tax.cents() as f64 * (1.0 + tax_rate.unwrap_or(0.0) / 100.0);
+    Money::from_cents(cents.round() as i64)
+}
+
+pub fn savings(price: Money, original_price: Option<Money>, discount: Option<Money>) -> Money {
+    (original_price.unwrap_or(price) - price).max(Money::ZERO) + discount.unwrap_or_default()
+}
+
+// Reads an optional amount input, None when blank
+pub fn parse_optional_amount(input: &str) -> Result<Option<Money>, String> {
+    if input.trim().is_empty() {
+        return Ok(None);
+    }
+    let amount = Money::parse(input)?;
+    if amount < Money::ZERO {
+        return Err(format!("amount \"{}\" cannot be negative", input.trim()));
+    }
+    Ok(Some(amount))
+}
+
+// Reads a tax rate in percent such as "8.25", "8,25" or "8.25%", None when blank
+pub fn parse_tax_rate(input: &str) -> Result<Option<f64>, String> {
+    let text = input.trim().trim_end_matches('%').trim().replace(',', ".");
+    if text.is_empty() {
+        return Ok(None);
+    }
+    match text.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && (0.0..100.0).contains(&rate) => Ok(Some(rate)),
+        _ => Err(format!(
+            "invalid tax rate \"{}\", expected a percentage like 8.25",
+            input.trim()
+        )),
+    }
+}
+
+// Checks the optional fields of a new purchase against its price
+pub fn validate(
+    price: Money,
+    original_price: Option<Money>,
+    discount: Option<Money>,
+) -> Result<(), String> {
+    if discount.is_some_and(|discount| discount > price) {
+        return Err("the discount cannot be more than the price".to_string());
+    }
+    if original_price.is_some_and(|original| original < price) {
+        return Err("the original price cannot be less than the price paid".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(cents: i64) -> Money {
+        Money::from_cents(cents)
+    }
+
+    #[test]
+    fn net_price_takes_off_the_discount_before_tax() {
+        assert_eq!(net_price(cents(1000), None, None), cents(1000));
+        assert_eq!(net_price(cents(1000), Some(cents(200)), None), cents(800));
+        assert_eq!(
+            net_price(cents(1000), Some(cents(200)), Some(10.0)),
+            cents(880)
+        );
+        // 4.99 at 8.25% is 5.4017, rounded to the nearest cent
+        assert_eq!(net_price(cents(499), None, Some(8.25)), cents(540));
+    }
+
+    #[test]
+    fn savings_count_the_sale_and_the_discount() {
+        assert_eq!(savings(cents(400), None, None), Money::ZERO);
+        assert_eq!(savings(cents(400), Some(cents(500)), None), cents(100));
+        assert_eq!(
+            savings(cents(400), Some(cents(500)), Some(cents(50))),
+            cents(150)
+        );
+        // An original price below the price paid is not a saving
+        assert_eq!(savings(cents(400), Some(cents(300)), None), Money::ZERO);
+    }
+
+    #[test]
+    fn parses_tax_rates() {
+        assert_eq!(parse_tax_rate(""), Ok(None));
+        assert_eq!(parse_tax_rate("8.25"), Ok(Some(8.25)));
+        assert_eq!(parse_tax_rate("8,25"), Ok(Some(8.25)));
+        assert_eq!(parse_tax_rate(" 8.25 % "), Ok(Some(8.25)));
+        for input in ["-1", "100", "abc", "NaN", "inf"] {
+            assert!(parse_tax_rate(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_optional_amounts() {
+        assert_eq!(parse_optional_amount("  "), Ok(None));
+        assert_eq!(parse_optional_amount("1.50"), Ok(Some(cents(150))));
+        assert!(parse_optional_amount("-1.50")
+            .unwrap_err()
+            .contains("cannot be negative"));
+    }
+
+    #[test]
+    fn validates_against_the_price() {
+        assert!(validate(cents(500), Some(cents(600)), Some(cents(500))).is_ok());
+        assert!(validate(cents(500), None, Some(cents(501))).is_err());
+        assert!(validate(cents(500), Some(cents(499)), None).is_err());
+    }
+
+    #[test]
+    fn sql_rounds_the_same_as_rust() {
+        let conn = sqlite::open(":memory:").unwrap();
+        conn.execute(
+            "CREATE TABLE purchase (price int, originalPrice int, discount int, taxRate real)",
+        )
+        .unwrap();
+
+        let prices = [1, 5, 99, 499, 1005, 12345];
+        let discounts = [None, Some(0), Some(1), Some(4)];
+        let originals = [None, Some(0), Some(600), Some(20000)];
+        let rates = [
+            None,
+            Some(0.0),
+            Some(5.0),
+            Some(6.25),
+            Some(8.25),
+            Some(10.0),
+            Some(12.5),
+        ];
+
+        for price in prices {
+            for discount in discounts {
+                for original in originals {
+                    for rate in rates {
+                        conn.execute("DELETE FROM purchase").unwrap();
+                        let mut statement = conn
+                            .prepare("INSERT INTO purchase VALUES (?, ?, ?, ?)")
+                            .unwrap();
+                        statement.bind((1, price)).unwrap();
+                        statement.bind((2, original)).unwrap();
+                        statement.bind((3, discount)).unwrap();
+                        statement.bind((4, rate)).unwrap();
+                        statement.next().unwrap();
+
+                        let mut statement = conn
+                            .prepare(format!(
+                                "SELECT {} AS net, {} AS saved FROM purchase",
+                                NET_PRICE_SQL, SAVINGS_SQL
+                            ))
+                            .unwrap();
+                        statement.next().unwrap();
+
+                        let (price, original, discount) =
+                            (cents(price), original.map(cents), discount.map(cents));
+                        assert_eq!(
+                            statement.read::<i64, _>("net").unwrap(),
+                            net_price(price, discount, rate).cents(),
+                            "{:?} {:?} {:?}",
+                            price,
+                            discount,
+                            rate
+                        );
+                        assert_eq!(
+                            statement.read::<i64, _>("saved").unwrap(),
+                            savings(price, original, discount).cents(),
+                            "{:?} {:?} {:?}",
+                            price,
+                            original,
+                            discount
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,511 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use sqlite::Value;
+
+use crate::app::resolve_date_input;
+use crate::category;
+use crate::currency::{self, home_price_sql};
+use crate::money::Money;
+use crate::pricing;
+
+/// A parsed search, `clause` holds `?` placeholders filled from `params` in order
+#[derive(Clone)]
+pub struct Filter {
+    pub clause: String,
+    pub params: Vec<Value>,
+    /// Words matched against the ingredient and store, used to rank and highlight results
+    pub terms: Vec<String>,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            clause: "true".to_string(),
+            params: Vec::new(),
+            terms: Vec::new(),
+        }
+    }
+}
+
+enum Token {
+    /// Search term, `quoted` when any part of it was in quotes so `"OR"` is not read as an operator
+    Word {
+        text: String,
+        quoted: bool,
+    },
+    Open,
+    Close,
+}
+
+// Splits the search on whitespace and parentheses, keeping quoted text together
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+
+    let finish = |text: &mut String, quoted: &mut bool, tokens: &mut Vec<Token>| {
+        if !text.is_empty() || *quoted {
+            tokens.push(Token::Word {
+                text: std::mem::take(text),
+                quoted: *quoted,
+            });
+        }
+        *quoted = false;
+    };
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            _ if in_quotes => text.push(c),
+            '(' | ')' => {
+                finish(&mut text, &mut quoted, &mut tokens);
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            _ if c.is_whitespace() => finish(&mut text, &mut quoted, &mut tokens),
+            _ => text.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("missing closing quote".to_string());
+    }
+    finish(&mut text, &mut quoted, &mut tokens);
+
+    Ok(tokens)
+}
+
+// Parses a search such as `milk price>3 after:2024-09-01 unexpended cat:dairy store:aldi`
+//
+// Terms next to each other must all match, `OR` matches either side, `NOT` or a leading `-`
+// negates the next term and parentheses group. Bare words fuzzy match the ingredient or store,
+// quoted text must appear in the ingredient as written. Dates also accept t, y, week, month,
+// year and a number of days ago like 30d. Amounts compare in the home currency, and purchases
+// made in the home currency match `currency:` with its code.
+pub fn parse(input: &str) -> Result<Filter, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Filter::default());
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        params: Vec::new(),
+        terms: Vec::new(),
+        negated: false,
+    };
+    let clause = parser.or()?;
+    if parser.position < parser.tokens.len() {
+        return Err("unexpected \")\"".to_string());
+    }
+
+    Ok(Filter {
+        clause,
+        params: parser.params,
+        terms: parser.terms,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    params: Vec<Value>,
+    terms: Vec<String>,
+    /// Inside a `NOT`, where matched words should not be highlighted
+    negated: bool,
+}
+
+impl Parser {
+    fn is_operator(&self, operator: &str) -> bool {
+        matches!(self.tokens.get(self.position), Some(Token::Word { text, quoted: false }) if text.as_str() == operator)
+    }
+
+    fn or(&mut self) -> Result<String, String> {
+        let mut clauses = vec![self.and()?];
+        while self.is_operator("OR") {
+            self.position += 1;
+            clauses.push(self.and()?);
+        }
+
+        Ok(if clauses.len() == 1 {
+            clauses.remove(0)
+        } else {
+            format!("({})", clauses.join(" OR "))
+        })
+    }
+
+    fn and(&mut self) -> Result<String, String> {
+        let mut clauses = Vec::new();
+        loop {
+            match self.tokens.get(self.position) {
+                None | Some(Token::Close) => break,
+                _ if self.is_operator("OR") => break,
+                _ if self.is_operator("AND") => self.position += 1,
+                _ => clauses.push(self.unary()?),
+            }
+        }
+
+        match clauses.len() {
+            0 => Err("expected a search term".to_string()),
+            1 => Ok(clauses.remove(0)),
+            _ => Ok(format!("({})", clauses.join(" AND "))),
+        }
+    }
+
+    fn unary(&mut self) -> Result<String, String> {
+        if self.is_operator("NOT") {
+            self.position += 1;
+            return Ok(format!("NOT {}", self.negate(|parser| parser.unary())?));
+        }
+
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or("expected a search term")?;
+        self.position += 1;
+
+        match token {
+            Token::Open => {
+                let clause = self.or()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(clause)
+                    }
+                    _ => Err("missing \")\"".to_string()),
+                }
+            }
+            Token::Close => Err("unexpected \")\"".to_string()),
+            Token::Word { text, quoted: true } => {
+                let text = text.clone();
+                self.add_term(&text);
+                Ok(self.like("ingredient", &text))
+            }
+            Token::Word {
+                text,
+                quoted: false,
+            } => {
+                let text = text.clone();
+                match text.strip_prefix('-') {
+                    Some(rest) if !rest.is_empty() => Ok(format!(
+                        "NOT ({})",
+                        self.negate(|parser| parser.term(rest))?
+                    )),
+                    _ => self.term(&text),
+                }
+            }
+        }
+    }
+
+    fn negate(
+        &mut self,
+        parse: impl FnOnce(&mut Parser) -> Result<String, String>,
+    ) -> Result<String, String> {
+        let negated = self.negated;
+        self.negated = !negated;
+        let clause = parse(self);
+        self.negated = negated;
+        clause
+    }
+
+    fn add_term(&mut self, text: &str) {
+        if !self.negated {
+            self.terms.push(text.to_string());
+        }
+    }
+
+    // Contains `text`, with any % and _ in it matched literally rather than as wildcards
+    fn like(&mut self, column: &str, text: &str) -> String {
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        self.params.push(Value::String(format!("%{}%", escaped)));
+        format!("{} LIKE ? ESCAPE '\\'", column)
+    }
+
+    // Compiles one `field<op>value` term, or a bare word matched against the ingredient
+    fn term(&mut self, text: &str) -> Result<String, String> {
+        match text {
+            "unexpended" => {
+                return Ok("(expendedDate IS NULL OR expendedDate = 'NULL')".to_string())
+            }
+            "expended" => {
+                return Ok("(expendedDate IS NOT NULL AND expendedDate != 'NULL')".to_string())
+            }
+            _ => {}
+        }
+
+        let Some(split) = text.find([':', '<', '>', '=', '!']) else {
+            self.add_term(text);
+            self.params.push(Value::String(text.to_string()));
+            return Ok("fuzzy(?, ingredient, store) IS NOT NULL".to_string());
+        };
+        let field = text[..split].to_lowercase();
+        let rest = &text[split..];
+        let operator = [">=", "<=", "!=", ":", "=", "<", ">"]
+            .into_iter()
+            .find(|operator| rest.starts_with(operator))
+            .ok_or_else(|| format!("unknown operator in \"{}\"", text))?;
+        let value = &rest[operator.len()..];
+        if value.is_empty() {
+            return Err(format!("missing value after \"{}\"", text));
+        }
+
+        match field.as_str() {
+            "ingredient" | "name" => self.text_term("ingredient", operator, value),
+            "store" => self.text_term("store", operator, value),
+            "paid" | "paidby" => self.text_term("paidBy", operator, value),
+            "split" => self.text_term("splitBetween", operator, value),
+            "cat" | "category" => {
+                if !category::CATEGORIES.contains(&value.to_lowercase().as_str()) {
+                    return Err(format!(
+                        "unknown category \"{}\", try one of {}",
+                        value,
+                        category::CATEGORIES.join(", ")
+                    ));
+                }
+                let operator = match operator {
+                    ":" | "=" => "=",
+                    "!=" => "!=",
+                    _ => return Err(format!("cat only supports : and !=, not {}", operator)),
+                };
+                self.params.push(Value::String(value.to_lowercase()));
+                Ok(format!(
+                    "{} {} ?",
+                    category::sql_expression("ingredient"),
+                    operator
+                ))
+            }
+            "price" => self.amount_term(&home_price_sql(), &field, operator, value),
+            "original" | "originalprice" => self.amount_term(
+                &currency::home_sql("purchase.originalPrice"),
+                &field,
+                operator,
+                value,
+            ),
+            "discount" => self.amount_term(
+                &currency::home_sql("purchase.discount"),
+                &field,
+                operator,
+                value,
+            ),
+            "tax" | "taxrate" => {
+                let rate = pricing::parse_tax_rate(value)?.unwrap_or(0.0);
+                self.params.push(Value::Float(rate));
+                Ok(format!("coalesce(taxRate, 0) {} ?", sql_operator(operator)))
+            }
+            "currency" => {
+                let operator = match operator {
+                    ":" | "=" => "=",
+                    "!=" => "!=",
+                    _ => return Err(format!("currency only supports : and !=, not {}", operator)),
+                };
+                self.params.push(Value::String(currency::normalize(value)?));
+                Ok(format!(
+                    "coalesce(currency, {}) {} ?",
+                    currency::home_code_sql(),
+                    operator
+                ))
+            }
+            "id" => {
+                let id = value
+                    .parse::<i64>()
+                    .map_err(|_| format!("expected a number after \"{}{}\"", field, operator))?;
+                self.params.push(Value::Integer(id));
+                Ok(format!("rowid {} ?", sql_operator(operator)))
+            }
+            "purchased" | "date" => self.date_term("purchaseDate", sql_operator(operator), value),
+            "expended" => {
+                let clause = self.date_term("expendedDate", sql_operator(operator), value)?;
+                Ok(format!("(expendedDate != 'NULL' AND {})", clause))
+            }
+            // Purchased on or after the date / strictly before it
+            "after" | "before" if operator == ":" => {
+                let operator = if field == "after" { ">=" } else { "<" };
+                self.date_term("purchaseDate", operator, value)
+            }
+            "after" | "before" => Err(format!(
+                "{} only supports :, e.g. {}:2024-09-01",
+                field, field
+            )),
+            _ => Err(format!("unknown field \"{}\"", field)),
+        }
+    }
+
+    fn amount_term(
+        &mut self,
+        amount_sql: &str,
+        field: &str,
+        operator: &str,
+        value: &str,
+    ) -> Result<String, String> {
+        let amount = Money::parse(value)
+            .map_err(|_| format!("expected an amount after \"{}{}\"", field, operator))?;
+        self.params.push(Value::Integer(amount.cents()));
+        Ok(format!("{} {} ?", amount_sql, sql_operator(operator)))
+    }
+
+    fn text_term(&mut self, column: &str, operator: &str, value: &str) -> Result<String, String> {
+        match operator {
+            ":" => Ok(self.like(column, value)),
+            "=" | "!=" => {
+                self.params.push(Value::String(value.to_string()));
+                Ok(format!("{} {} ? COLLATE NOCASE", column, operator))
+            }
+            _ => Err(format!(
+                "{} only supports :, = and !=, not {}",
+                column, operator
+            )),
+        }
+    }
+
+    fn date_term(&mut self, column: &str, operator: &str, value: &str) -> Result<String, String> {
+        let date = resolve_date(value)
+            .ok_or_else(|| format!("invalid date \"{}\", expected YYYY-MM-DD", value))?;
+
+        self.params
+            .push(Value::String(date.format("%Y-%m-%d").to_string()));
+        Ok(format!("{} {} ?", column, operator))
+    }
+}
+
+// Dates as YYYY-MM-DD, t/y, the start of the current week/month/year, or a number of days ago like 30d,
+// so saved views such as "after:month" keep following the calendar
+fn resolve_date(value: &str) -> Option<NaiveDate> {
+    let today = Local::now().date_naive();
+
+    match value.to_lowercase().as_str() {
+        "week" => Some(today - Duration::days(today.weekday().num_days_from_monday() as i64)),
+        "month" => today.with_day(1),
+        "year" => today.with_ordinal(1),
+        days if days.ends_with('d') => days[..days.len() - 1]
+            .parse::<i64>()
+            .ok()
+            .map(|days| today - Duration::days(days)),
+        _ => NaiveDate::parse_from_str(&resolve_date_input(value), "%Y-%m-%d").ok(),
+    }
+}
+
+fn sql_operator(operator: &str) -> &str {
+    match operator {
+        ":" => "=",
+        operator => operator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_params(filter: &Filter) -> Vec<String> {
+        filter
+            .params
+            .iter()
+            .map(|param| match param {
+                Value::String(text) => text.clone(),
+                Value::Integer(number) => number.to_string(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_search_matches_everything() {
+        assert_eq!(parse("  ").unwrap().clause, "true");
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let filter = parse("id=1 id=2 OR id=3").unwrap();
+        assert_eq!(filter.clause, "((rowid = ? AND rowid = ?) OR rowid = ?)");
+        assert_eq!(text_params(&filter), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn parentheses_group_before_and() {
+        let filter = parse("id=1 (id=2 OR id=3)").unwrap();
+        assert_eq!(filter.clause, "(rowid = ? AND (rowid = ? OR rowid = ?))");
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term_only() {
+        assert_eq!(
+            parse("NOT id=1 id=2").unwrap().clause,
+            "(NOT rowid = ? AND rowid = ?)"
+        );
+        assert_eq!(
+            parse("-id=1 OR id=2").unwrap().clause,
+            "(NOT (rowid = ?) OR rowid = ?)"
+        );
+    }
+
+    #[test]
+    fn quoted_operators_are_search_words() {
+        let filter = parse("\"OR\"").unwrap();
+        assert_eq!(filter.clause, "ingredient LIKE ? ESCAPE '\\'");
+        assert_eq!(text_params(&filter), ["%OR%"]);
+    }
+
+    #[test]
+    fn like_wildcards_match_literally() {
+        let filter = parse("store:50%_off").unwrap();
+        assert_eq!(filter.clause, "store LIKE ? ESCAPE '\\'");
+        assert_eq!(text_params(&filter), ["%50\\%\\_off%"]);
+    }
+
+    #[test]
+    fn negated_words_are_not_highlighted() {
+        let filter = parse("milk -eggs NOT \"bread\"").unwrap();
+        assert_eq!(filter.terms, ["milk"]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |input: &str| parse(input).err().unwrap();
+        assert_eq!(error("(milk"), "missing \")\"");
+        assert_eq!(error("milk)"), "unexpected \")\"");
+        assert_eq!(error("\"milk"), "missing closing quote");
+        assert_eq!(error("milk OR"), "expected a search term");
+        assert_eq!(error("price>"), "missing value after \"price>\"");
+        assert_eq!(error("price>abc"), "expected an amount after \"price>\"");
+        assert_eq!(error("color:red"), "unknown field \"color\"");
+        assert_eq!(
+            error("store>aldi"),
+            "store only supports :, = and !=, not >"
+        );
+        assert_eq!(
+            error("after>2024-01-01"),
+            "after only supports :, e.g. after:2024-09-01"
+        );
+        assert_eq!(
+            error("date:someday"),
+            "invalid date \"someday\", expected YYYY-MM-DD"
+        );
+        assert!(error("cat:shoes").starts_with("unknown category \"shoes\""));
+    }
+
+    #[test]
+    fn pricing_and_currency_fields() {
+        let filter = parse("discount>=1 tax:0").unwrap();
+        assert!(filter.clause.contains("purchase.discount"));
+        assert!(filter.clause.contains("coalesce(taxRate, 0) = ?"));
+        assert_eq!(text_params(&filter)[0], "100");
+
+        assert!(parse("original<5.00")
+            .unwrap()
+            .clause
+            .contains("purchase.originalPrice"));
+
+        let filter = parse("currency:eur").unwrap();
+        assert!(filter.clause.starts_with("coalesce(currency, "));
+        assert_eq!(text_params(&filter), ["EUR"]);
+
+        let error = |input: &str| parse(input).err().unwrap();
+        assert!(error("currency>EUR").starts_with("currency only supports"));
+        assert!(error("currency:euro").starts_with("invalid currency"));
+        assert!(error("tax>abc").starts_with("invalid tax rate"));
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+
+use chrono::{Datelike, Duration, Local, Months, NaiveDate};
+use clap::ValueEnum;
+use sqlite::State;
+
+use crate::app::{App, MealSwipeInfo};
+use crate::category::categorize;
+use crate::currency::home_price_sql;
+use crate::db;
+use crate::money::Money;
+
+/// Days an item can sit unexpended before the report counts it as waste
+const WASTE_AFTER_DAYS: i64 = 30;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Period {
+    Week,
+    Month,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+pub struct Report {
+    pub title: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub total: Money,
+    pub purchases: usize,
+    /// Saved through sales and coupons on the period's purchases
+    pub savings: Money,
+    /// (category, spend) sorted by spend
+    pub categories: Vec<(String, Money)>,
+    /// (ingredient, spend, times bought) sorted by spend
+    pub top_ingredients: Vec<(String, Money, usize)>,
+    /// (ingredient, price) bought in the period and still unexpended WASTE_AFTER_DAYS later
+    pub waste: Vec<(String, Money)>,
+    pub swipes: MealSwipeInfo,
+    pub previous_total: Money,
+}
+
+// First and last day of the period containing `date`
+pub fn period_bounds(period: Period, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match period {
+        Period::Week => {
+            let start = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+            (start, start + Duration::days(6))
+        }
+        Period::Month => {
+            let start = date.with_day(1).unwrap();
+            (start, start + Months::new(1) - Duration::days(1))
+        }
+    }
+}
+
+fn previous_bounds(period: Period, start: NaiveDate) -> (NaiveDate, NaiveDate) {
+    period_bounds(period, start - Duration::days(1))
+}
+
+// (ingredient, price, purchase date, expended date) for purchases made in [start, end]
+fn read_purchases(
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<(String, Money, NaiveDate, Option<NaiveDate>)> {
+    let conn = db::open();
+    let query = format!("SELECT ingredient, {} AS price, purchaseDate, expendedDate FROM purchase WHERE purchaseDate BETWEEN ? AND ?", home_price_sql());
+    let mut statement = conn.prepare(query).unwrap();
+    statement.bind((1, start.to_string().as_str())).unwrap();
+    statement.bind((2, end.to_string().as_str())).unwrap();
+
+    let mut purchases = Vec::new();
+    while let State::Row = statement.next().unwrap() {
+        let purchase_date = statement
+            .read::<String, _>("purchaseDate")
+            .unwrap_or_default();
+        let Ok(purchase_date) = NaiveDate::parse_from_str(&purchase_date, "%Y-%m-%d") else {
+            continue;
+        };
+        let expended_date = statement
+            .read::<String, _>("expendedDate")
+            .ok()
+            .and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+
+        purchases.push((
+            statement
+                .read::<String, _>("ingredient")
+                .unwrap_or_default(),
+            Money::from_cents(statement.read::<i64, _>("price").unwrap_or(0)),
+            purchase_date,
+            expended_date,
+        ));
+    }
+
+    purchases
+}
+
+pub fn build(period: Period, date: NaiveDate) -> Report {
+    let (start, end) = period_bounds(period, date);
+    let (previous_start, previous_end) = previous_bounds(period, start);
+    let purchases = read_purchases(start, end);
+    let today = Local::now().date_naive();
+
+    let mut categories = HashMap::<String, Money>::new();
+    let mut ingredients = HashMap::<String, (Money, usize)>::new();
+    let mut waste = Vec::<(String, Money)>::new();
+
+    for (ingredient, price, purchase_date, expended_date) in &purchases {
+        *categories
+            .entry(categorize(ingredient).to_string())
+            .or_default() += *price;
+
+        let entry = ingredients.entry(ingredient.clone()).or_default();
+        entry.0 += *price;
+        entry.1 += 1;
+
+        let waste_date = *purchase_date + Duration::days(WASTE_AFTER_DAYS);
+        if waste_date < today && expended_date.is_none_or(|expended| expended > waste_date) {
+            waste.push((ingredient.clone(), *price));
+        }
+    }
+
+    let mut categories: Vec<(String, Money)> = categories.into_iter().collect();
+    categories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut top_ingredients: Vec<(String, Money, usize)> = ingredients
+        .into_iter()
+        .map(|(ingredient, (spend, count))| (ingredient, spend, count))
+        .collect();
+    top_ingredients.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    top_ingredients.truncate(5);
+
+    let title = match period {
+        Period::Week => format!("Week of {}", start.format("%Y-%m-%d")),
+        Period::Month => start.format("%B %Y").to_string(),
+    };
+
+    Report {
+        title,
+        start,
+        end,
+        total: purchases.iter().map(|purchase| purchase.1).sum(),
+        purchases: purchases.len(),
+        savings: App::get_period_savings(start, end),
+        categories,
+        top_ingredients,
+        waste,
+        swipes: App::get_period_meal_swipe_estimate(start, end),
+        previous_total: read_purchases(previous_start, previous_end)
+            .iter()
+            .map(|purchase| purchase.1)
+            .sum(),
+    }
+}
+
+fn comparison(report: &Report) -> String {
+    let difference = report.total - report.previous_total;
+    if report.previous_total == Money::ZERO {
+        format!("{} vs nothing last period", report.total)
+    } else {
+        let percent = difference.cents() as f64 / report.previous_total.cents() as f64 * 100.0;
+        let direction = if difference >= Money::ZERO {
+            "up"
+        } else {
+            "down"
+        };
+        format!(
+            "{} {} ({:.0}%) from {}",
+            direction,
+            difference.abs(),
+            percent.abs(),
+            report.previous_total
+        )
+    }
+}
+
+pub fn write_markdown<W: Write>(report: &Report, mut writer: W) -> Result<(), Box<dyn Error>> {
+    writeln!(writer, "# Food Spending Report: {}", report.title)?;
+    writeln!(writer)?;
+    writeln!(writer, "{} to {}", report.start, report.end)?;
+    writeln!(writer)?;
+    writeln!(writer, "## Totals")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "- Grocery spend: {} across {} purchases",
+        report.total, report.purchases
+    )?;
+    writeln!(writer, "- Saved with sales and coupons: {}", report.savings)?;
+    writeln!(
+        writer,
+        "- Compared to previous period: {}",
+        comparison(report)
+    )?;
+    writeln!(
+        writer,
+        "- Meal swipes used: {} ({} at swipe price)",
+        report.swipes.swipes, report.swipes.cost
+    )?;
+    writeln!(writer)?;
+
+    writeln!(writer, "## Categories")?;
+    writeln!(writer)?;
+    writeln!(writer, "| Category | Spend |")?;
+    writeln!(writer, "| --- | ---: |")?;
+    for (category, spend) in &report.categories {
+        writeln!(writer, "| {} | {} |", category, spend)?;
+    }
+    writeln!(writer)?;
+
+    writeln!(writer, "## Top Ingredients")?;
+    writeln!(writer)?;
+    writeln!(writer, "| Ingredient | Times Bought | Spend |")?;
+    writeln!(writer, "| --- | ---: | ---: |")?;
+    for (ingredient, spend, count) in &report.top_ingredients {
+        writeln!(
+            writer,
+            "| {} | {} | {} |",
+            ingredient.replace('|', "\\|"),
+            count,
+            spend
+        )?;
+    }
+    writeln!(writer)?;
+
+    writeln!(writer, "## Waste")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "Items not expended within {} days of purchase: {}",
+        WASTE_AFTER_DAYS,
+        report.waste.iter().map(|item| item.1).sum::<Money>()
+    )?;
+    writeln!(writer)?;
+    for (ingredient, price) in &report.waste {
+        writeln!(writer, "- {} ({})", ingredient, price)?;
+    }
+
+    Ok(())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn write_html<W: Write>(report: &Report, mut writer: W) -> Result<(), Box<dyn Error>> {
+    let title = escape_html(&report.title);
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(
+        writer,
+        "<html><head><meta charset=\"utf-8\"><title>Food Spending Report: {}</title>",
+        title
+    )?;
+    writeln!(writer, "<style>body{{font-family:sans-serif;max-width:50em;margin:2em auto;color:#1e293b}}table{{border-collapse:collapse}}td,th{{padding:.25em 1em;border-bottom:1px solid #cbd5e1;text-align:left}}td.num{{text-align:right}}</style>")?;
+    writeln!(writer, "</head><body>")?;
+    writeln!(writer, "<h1>Food Spending Report: {}</h1>", title)?;
+    writeln!(writer, "<p>{} to {}</p>", report.start, report.end)?;
+
+    writeln!(writer, "<h2>Totals</h2><ul>")?;
+    writeln!(
+        writer,
+        "<li>Grocery spend: {} across {} purchases</li>",
+        report.total, report.purchases
+    )?;
+    writeln!(
+        writer,
+        "<li>Saved with sales and coupons: {}</li>",
+        report.savings
+    )?;
+    writeln!(
+        writer,
+        "<li>Compared to previous period: {}</li>",
+        comparison(report)
+    )?;
+    writeln!(
+        writer,
+        "<li>Meal swipes used: {} ({} at swipe price)</li>",
+        report.swipes.swipes, report.swipes.cost
+    )?;
+    writeln!(writer, "</ul>")?;
+
+    writeln!(
+        writer,
+        "<h2>Categories</h2><table><tr><th>Category</th><th>Spend</th></tr>"
+    )?;
+    for (category, spend) in &report.categories {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td class=\"num\">{}</td></tr>",
+            category, spend
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Top Ingredients</h2><table><tr><th>Ingredient</th><th>Times Bought</th><th>Spend</th></tr>")?;
+    for (ingredient, spend, count) in &report.top_ingredients {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            escape_html(ingredient),
+            count,
+            spend
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Waste</h2>")?;
+    writeln!(
+        writer,
+        "<p>Items not expended within {} days of purchase: {}</p><ul>",
+        WASTE_AFTER_DAYS,
+        report.waste.iter().map(|item| item.1).sum::<Money>()
+    )?;
+    for (ingredient, price) in &report.waste {
+        writeln!(writer, "<li>{} ({})</li>", escape_html(ingredient), price)?;
+    }
+    writeln!(writer, "</ul></body></html>")?;
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use sqlite::State;
+
+use crate::app::parse_names;
+use crate::currency::home_price_sql;
+use crate::db;
+use crate::money::Money;
+
+pub struct SettleUp {
+    /// (person, amount) paid minus owed, positive when others owe them
+    pub balances: Vec<(String, Money)>,
+    /// (from, to, amount) payments that settle every balance
+    pub transfers: Vec<(String, String, Money)>,
+}
+
+pub fn people() -> Vec<String> {
+    let conn = db::open();
+    let mut statement = conn
+        .prepare("SELECT name FROM person ORDER BY name")
+        .unwrap();
+    let mut people = Vec::<String>::new();
+
+    while let State::Row = statement.next().unwrap() {
+        people.push(statement.read::<String, _>("name").unwrap());
+    }
+
+    people
+}
+
+// Nets out every shared purchase and pairs the largest debtors with the largest creditors
+pub fn settle_up() -> SettleUp {
+    let conn = db::open();
+    let mut balances = BTreeMap::<String, Money>::new();
+
+    for person in people() {
+        balances.insert(person, Money::ZERO);
+    }
+
+    let query = format!("SELECT {} AS price, paidBy, splitBetween FROM purchase WHERE paidBy IS NOT NULL AND splitBetween IS NOT NULL", home_price_sql());
+    let mut statement = conn.prepare(query).unwrap();
+
+    while let State::Row = statement.next().unwrap() {
+        let price = Money::from_cents(statement.read::<i64, _>("price").unwrap_or(0));
+        let paid_by = statement.read::<String, _>("paidBy").unwrap_or_default();
+        let split_between = parse_names(
+            &statement
+                .read::<String, _>("splitBetween")
+                .unwrap_or_default(),
+        );
+        if split_between.is_empty() {
+            continue;
+        }
+
+        *balances.entry(paid_by).or_default() += price;
+
+        // Leftover cents go to the first people in the split so shares always add up to the price
+        let shares = price.split(split_between.len());
+        for (person, share) in split_between.into_iter().zip(shares) {
+            *balances.entry(person).or_default() -= share;
+        }
+    }
+
+    let mut debtors: Vec<(String, Money)> = balances
+        .iter()
+        .filter(|(_, &owed)| owed < Money::ZERO)
+        .map(|(name, &owed)| (name.clone(), -owed))
+        .collect();
+    let mut creditors: Vec<(String, Money)> = balances
+        .iter()
+        .filter(|(_, &owed)| owed > Money::ZERO)
+        .map(|(name, &owed)| (name.clone(), owed))
+        .collect();
+    debtors.sort_by_key(|debtor| Reverse(debtor.1));
+    creditors.sort_by_key(|creditor| Reverse(creditor.1));
+
+    let mut transfers = Vec::<(String, String, Money)>::new();
+    let (mut d, mut c) = (0, 0);
+    while d < debtors.len() && c < creditors.len() {
+        let amount = debtors[d].1.min(creditors[c].1);
+        transfers.push((debtors[d].0.clone(), creditors[c].0.clone(), amount));
+
+        debtors[d].1 -= amount;
+        creditors[c].1 -= amount;
+        if debtors[d].1 == Money::ZERO {
+            d += 1;
+        }
+        if creditors[c].1 == Money::ZERO {
+            c += 1;
+        }
+    }
+
+    SettleUp {
+        balances: balances.into_iter().collect(),
+        transfers,
+    }
+}
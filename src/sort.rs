@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::column::Column;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub column: Column,
+    pub direction: Direction,
+}
+
+/// Columns to sort the transactions by, most significant first
+#[derive(Clone, PartialEq)]
+pub struct Sort {
+    pub keys: Vec<SortKey>,
+}
+
+impl Default for Sort {
+    // Unexpended items first, oldest purchases at the top
+    fn default() -> Sort {
+        Sort {
+            keys: vec![
+                SortKey {
+                    column: Column::ExpendedDate,
+                    direction: Direction::Desc,
+                },
+                SortKey {
+                    column: Column::PurchaseDate,
+                    direction: Direction::Asc,
+                },
+            ],
+        }
+    }
+}
+
+impl Sort {
+    pub fn by(column: Column, direction: Direction) -> Sort {
+        Sort {
+            keys: vec![SortKey { column, direction }],
+        }
+    }
+
+    // ORDER BY clause, ending on the rowid so rows that tie on every key keep a fixed order
+    pub fn order_by(&self) -> String {
+        let mut terms: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let direction = match key.direction {
+                    Direction::Asc => "ASC",
+                    Direction::Desc => "DESC",
+                };
+                format!("{} {}", key.column.sql(), direction)
+            })
+            .collect();
+        terms.push("rowid ASC".to_string());
+
+        terms.join(", ")
+    }
+
+    // Sorts by only this column, going ascending, descending, then back to the default sort
+    pub fn cycle(&mut self, column: Column) {
+        let direction = match self.keys.as_slice() {
+            [key] if key.column == column => match key.direction {
+                Direction::Asc => Some(Direction::Desc),
+                Direction::Desc => None,
+            },
+            _ => Some(Direction::Asc),
+        };
+
+        *self = match direction {
+            Some(direction) => Sort::by(column, direction),
+            None => Sort::default(),
+        };
+    }
+
+    // Adds the column as the least significant key, or flips it ascending to descending to removed
+    pub fn cycle_key(&mut self, column: Column) {
+        match self.keys.iter().position(|key| key.column == column) {
+            Some(i) if self.keys[i].direction == Direction::Asc => {
+                self.keys[i].direction = Direction::Desc
+            }
+            Some(i) => {
+                self.keys.remove(i);
+            }
+            None => self.keys.push(SortKey {
+                column,
+                direction: Direction::Asc,
+            }),
+        }
+
+        if self.keys.is_empty() {
+            *self = Sort::default();
+        }
+    }
+
+    // Header text for a column, with an arrow for its direction and its rank when sorting by several columns
+    pub fn header(&self, column: Column) -> String {
+        let Some(i) = self.keys.iter().position(|key| key.column == column) else {
+            return column.title().to_string();
+        };
+        let arrow = match self.keys[i].direction {
+            Direction::Asc => "▲",
+            Direction::Desc => "▼",
+        };
+
+        if self.keys.len() > 1 {
+            format!("{} {}{}", column.title(), arrow, i + 1)
+        } else {
+            format!("{} {}", column.title(), arrow)
+        }
+    }
+
+    // Reads a sort written by `Display`, e.g. "price DESC, ingredient ASC"
+    pub fn parse(text: &str) -> Result<Sort, String> {
+        let mut keys = Vec::new();
+
+        for term in text
+            .split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+        {
+            let mut words = term.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let column =
+                Column::parse(name).ok_or_else(|| format!("unknown sort column \"{}\"", name))?;
+            let direction = match words.next().map(str::to_lowercase).as_deref() {
+                None | Some("asc") => Direction::Asc,
+                Some("desc") => Direction::Desc,
+                Some(other) => return Err(format!("unknown sort direction \"{}\"", other)),
+            };
+
+            keys.push(SortKey { column, direction });
+        }
+
+        if keys.is_empty() {
+            return Ok(Sort::default());
+        }
+        Ok(Sort { keys })
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let direction = match key.direction {
+                    Direction::Asc => "ASC",
+                    Direction::Desc => "DESC",
+                };
+                format!("{} {}", key.column.name(), direction)
+            })
+            .collect();
+
+        write!(f, "{}", keys.join(", "))
+    }
+}
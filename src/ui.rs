@@ -1,13 +1,19 @@
+use std::collections::HashSet;
+
+use crate::app::{App, CurrentScreen, ItemInfo, MealPlan};
+use crate::column::{Column, ColumnSetting};
+use crate::fuzzy::fuzzy_match;
+use crate::money::Money;
+use crate::sort::Sort;
+use crate::{currency, db, settle};
+use ratatui::widgets::HighlightSpacing;
 use ratatui::{
-    layout::{Constraint, Layout, Rect, Flex},
-    style::{Color, Style, Stylize, Modifier, palette::tailwind},
-    text::{Line},
-    widgets::{Table, Borders, Block, Row, Paragraph, Clear},
+    layout::{Constraint, Flex, Layout, Rect},
     prelude::*,
+    style::{palette::tailwind, Style, Stylize},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Row, Table},
     Frame,
 };
-use ratatui::widgets::HighlightSpacing;
-use crate::app::{App, CurrentScreen, ItemInfo};
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
     // Create the layout sections.
@@ -17,84 +23,168 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         Constraint::Min(1),
         Constraint::Length(1),
     ])
-        .areas(frame.area());
+    .areas(frame.area());
 
     // Navbar layout
-    let [navbar_left, navbar_right] = Layout::horizontal([
-        Constraint::Min(1),
-        Constraint::Min(1),
-    ])
-        .areas(navbar_area);
+    let [navbar_left, navbar_right] =
+        Layout::horizontal([Constraint::Min(1), Constraint::Min(1)]).areas(navbar_area);
 
     // Divide layout in top/bottom
-    let [top_half, bottom_half] = Layout::vertical([
-        Constraint::Percentage(50),
-        Constraint::Percentage(50),
-    ])
-        .margin(1)
-        .areas(main_area);
+    let [top_half, bottom_half] =
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .margin(1)
+            .areas(main_area);
 
     // Top half layout
-    let [top_left, top_right] = Layout::horizontal([
-        Constraint::Percentage(50),
-        Constraint::Percentage(50),
-    ])
-        .areas(top_half);
+    let [top_left, top_right] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .areas(top_half);
 
     let stats_block = Block::bordered().title("Statistics\n");
     let graph_block = Block::bordered().title("Daily Spending (Month)");
     // let table_block = Block::bordered().title("Transactions");
 
-    let monthly_text:Line = vec![
-            "Month Meal Swipe Bill: ".into(),
-            format!("${:.2}", App::get_monthly_meal_swipe_estimate()).red()
-     ].into();
+    let monthly_text: Line = vec![
+        "Month Meal Swipe Bill: ".into(),
+        App::get_monthly_meal_swipe_estimate().to_string().red(),
+    ]
+    .into();
+
+    let semester_cost_text: Line = vec![
+        "Semester Meal Swipe Bill: ".into(),
+        App::get_semesterly_meal_swipe_estimate()
+            .cost
+            .to_string()
+            .red(),
+    ]
+    .into();
 
-    let semester_cost_text:Line = vec![
-            "Semester Meal Swipe Bill: ".into(),
-            format!("${:.2}", App::get_semesterly_meal_swipe_estimate().cost).red()
-        ].into();
+    let forecast = App::get_swipe_forecast();
 
-    let semester_count_text:Line = vec![
+    let semester_count_text: Line = vec![
         "Semester Meal Swipes Used: ".into(),
-        format!("{}/210", App::get_semesterly_meal_swipe_estimate().swipes).red()
-    ].into();
+        format!(
+            "{}/{}",
+            forecast.swipes_used,
+            MealPlan::load().semester_swipes
+        )
+        .red(),
+    ]
+    .into();
+
+    let forecast_text: Line = match forecast.run_out_date {
+        _ if forecast.burn_rate.is_none() => {
+            vec!["Swipe Forecast: ".into(), "record swipes with (w)".red()].into()
+        }
+        Some(date) => vec![
+            "Swipes Run Out: ".into(),
+            format!(
+                "{} ({} short)",
+                date.format("%Y-%m-%d"),
+                -forecast.projected_leftover
+            )
+            .red(),
+        ]
+        .into(),
+        None => vec![
+            "Projected Leftover Swipes: ".into(),
+            format!(
+                "{}/{}",
+                forecast.projected_leftover, forecast.swipes_remaining
+            )
+            .red(),
+        ]
+        .into(),
+    };
+
+    let recommended_text: Line = vec![
+        "Recommended Swipes/Week: ".into(),
+        format!("{:.1}", forecast.recommended_per_week).red(),
+    ]
+    .into();
+
+    let dining = App::get_dining_info();
+
+    let mut dining_text: Line = vec![
+        "Dining Dollars Left: ".into(),
+        format!(
+            "{}/{} ({}/day)",
+            dining.remaining, dining.starting_balance, dining.daily_allowance
+        )
+        .red(),
+    ]
+    .into();
+    if let Some(date) = dining.run_out_date {
+        dining_text.push_span(format!(", out by {}", date.format("%Y-%m-%d")).red());
+    }
+
+    let savings_text: Line = vec![
+        "Month Savings (Sales & Coupons): ".into(),
+        App::get_monthly_savings().to_string().green(),
+    ]
+    .into();
 
     let stat_text = vec![
         monthly_text,
+        savings_text,
         semester_cost_text,
         semester_count_text,
+        forecast_text,
+        recommended_text,
+        dining_text,
     ];
 
     use ratatui::{prelude::*, widgets::*};
 
-    app.row_data = App::get_ingredient_entries(app);
+    app.load_rows();
+    let visible_columns = app.columns.visible();
     let mut rows = Vec::<Row>::new();
-    let mut i = 0;
 
-    for row in app.row_data.clone() {
-        let color = match i % 2 {
+    for (i, row) in app.row_data.clone().into_iter().enumerate() {
+        let color = match (app.row_offset + i) % 2 {
             0 => Color::Reset,
-            _ => Color::from_u32(0x0d1823)
+            _ => Color::from_u32(0x0d1823),
         };
 
-        rows.push(Row::new(vec![
-            Cell::from(row[0].clone()),
-            Cell::from(row[1].clone()),
-            Cell::from(row[2].clone()),
-            Cell::from(row[3].clone()),
-            Cell::from(row[4].clone()),
-        ]).style(Style::new().fg(tailwind::SLATE.c200).bg(color)));
+        let cells: Vec<Cell> = visible_columns
+            .iter()
+            .map(|setting| match setting.column {
+                Column::Ingredient | Column::Store => {
+                    Cell::from(highlight(&setting.column.value(&row), &app.filter.terms))
+                }
+                column => Cell::from(column.value(&row)),
+            })
+            .collect();
 
-        i += 1;
+        rows.push(Row::new(cells).style(Style::new().fg(tailwind::SLATE.c200).bg(color)));
     }
-    // Columns widths are constrained in the same way as Layout...
-    let table = render_table(rows, app.query_input.clone());
+    // The footer is drawn below the table instead of as a table row so long searches and errors are not cut to the ID column
+    let table_block = Block::new().title("Transactions").borders(Borders::ALL);
+    let [table_area, footer_area] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)])
+        .areas(table_block.inner(bottom_half));
+    frame.render_widget(table_block, bottom_half);
+    // Less the header row and its margin
+    app.page_height = table_area.height.saturating_sub(2) as usize;
+
+    let table = render_table(rows, &visible_columns, &app.sort);
+    frame.render_stateful_widget(table, table_area, &mut app.state);
 
-    frame.render_stateful_widget(table, bottom_half, &mut app.state);
+    let [search_area, status_area] = Layout::horizontal([Constraint::Fill(2), Constraint::Fill(1)])
+        .areas(footer_area.inner(Margin::new(3, 0)));
+    let mut search_line = Line::from(format!("Search: {}", app.query_input));
+    if let Some(err) = &app.query_error {
+        search_line.push_span(Span::from(format!("  {}", err)).red());
+    }
+    frame.render_widget(Paragraph::new(search_line).light_blue(), search_area);
+    frame.render_widget(
+        Paragraph::new(app.status_message.clone())
+            .light_blue()
+            .right_aligned(),
+        status_area,
+    );
 
     frame.render_widget(
-        Paragraph::new("Meal Price Tracker")
+        Paragraph::new(format!("Meal Price Tracker ({})", db::profile()))
             .style(Style::new().black().on_blue())
             .centered(),
         header_area,
@@ -119,115 +209,840 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     );
 
     frame.render_widget(
-        Paragraph::new(" (q) to quit | (i) to add transaction | (e) to edit expended | (E) expended today | (r/R) to remove entry").style(Style::new().black().on_blue()),
+        Paragraph::new(" (q) to quit | (i) to add transaction | (e) to edit expended | (E) expended today | (w) swipe used | (a) meal plan analysis | (b/B) dining dollars | (t) templates | (l) shopping list").style(Style::new().black().on_blue()),
         navbar_left,
     );
 
     frame.render_widget(
-        Paragraph::new("(Q) to query | (Back) to reset | (p/P) sort by price | (d/D) sort by date | (s) smart sort ").style(Style::new().black().on_blue()).alignment(Alignment::Right),
+        Paragraph::new("(Q) to query | (Back) to reset | (x) export csv | (h/H) history | (u) settle up | (v) views | (c) compare stores | (o) profiles | (C) columns | (PgUp/PgDn/Home/End) scroll | (1-9) sort column | (Alt+1-9) add sort key | (p/P) price | (d/D) date | (s) smart sort ").style(Style::new().black().on_blue()).alignment(Alignment::Right),
         navbar_right,
     );
 
     if let Some(editing) = &app.currently_editing {
-
         let active_style = Style::default().bg(Color::LightBlue).fg(Color::Black);
 
-            match &app.current_screen {
-                CurrentScreen::SingleInput => {
-                    match editing {
-                        ItemInfo::PurchaseDate => {
-                            let popup_block = Block::default()
-                                .borders(Borders::NONE)
-                                .style(Style::default());
-
-                            let area = popup_area(frame.area(), 30, 6);
-                            frame.render_widget(Clear, area);
-                            frame.render_widget(popup_block, area);
-
-                            let popup_chunks = Layout::vertical([Constraint::Fill(1)])
-                                .margin(1)
-                                .split(area);
-
-                            let purchase_block = Block::default().title("Purchase Date").borders(Borders::ALL).style(active_style);
-                            let purchase_text = Paragraph::new(app.purchase_date_input.clone()).block(purchase_block);
-                            frame.render_widget(purchase_text, popup_chunks[0]);
-                        }
-                        _ => {
-                            let popup_block = Block::default()
-                                .borders(Borders::NONE)
-                                .style(Style::default());
-
-                            let area = popup_area(frame.area(), 30, 14);
-                            frame.render_widget(Clear, area);
-                            frame.render_widget(popup_block, area);
-
-                            let popup_chunks = Layout::vertical([Constraint::Fill(1); 3])
-                                .margin(1)
-                                .split(area);
-
-                            let mut ingredient_block = Block::default().title("Ingredient").borders(Borders::ALL);
-                            let mut price_block = Block::default().title("Price").borders(Borders::ALL);
-                            let mut expended_block = Block::default().title("Expended Date (Optional)").borders(Borders::ALL);
-
-                            match editing {
-                                ItemInfo::Ingredient => ingredient_block = ingredient_block.style(active_style),
-                                ItemInfo::Price => price_block = price_block.style(active_style),
-                                ItemInfo::ExpendedDate => expended_block = expended_block.style(active_style),
-                                _ => {}
-                            };
-
-                            let ingredient_text = Paragraph::new(app.ingredient_input.clone()).block(ingredient_block);
-                            frame.render_widget(ingredient_text, popup_chunks[0]);
-
-                            let price_text = Paragraph::new(app.price_input.clone()).block(price_block);
-                            frame.render_widget(price_text, popup_chunks[1]);
-
-                            let expended_text = Paragraph::new(app.expended_date_input.clone()).block(expended_block);
-                            frame.render_widget(expended_text, popup_chunks[2]);
+        match &app.current_screen {
+            CurrentScreen::SingleInput => {
+                match editing {
+                    ItemInfo::PurchaseDate => {
+                        let popup_block = Block::default()
+                            .borders(Borders::NONE)
+                            .style(Style::default());
+
+                        let area = popup_area(frame.area(), 30, 6);
+                        frame.render_widget(Clear, area);
+                        frame.render_widget(popup_block, area);
+
+                        let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                            .margin(1)
+                            .split(area);
+
+                        let purchase_block = Block::default()
+                            .title("Purchase Date")
+                            .borders(Borders::ALL)
+                            .style(active_style);
+                        let purchase_text =
+                            Paragraph::new(app.purchase_date_input.clone()).block(purchase_block);
+                        frame.render_widget(purchase_text, popup_chunks[0]);
+                    }
+                    _ => {
+                        let popup_block = Block::default()
+                            .borders(Borders::NONE)
+                            .style(Style::default());
+
+                        let area = popup_area(frame.area(), 30, 26);
+                        frame.render_widget(Clear, area);
+                        frame.render_widget(popup_block, area);
+
+                        let popup_chunks = Layout::vertical([Constraint::Fill(1); 8])
+                            .margin(1)
+                            .split(area);
+
+                        let mut ingredient_block =
+                            Block::default().title("Ingredient").borders(Borders::ALL);
+                        let mut price_block = Block::default().title("Price").borders(Borders::ALL);
+                        let mut original_price_block = Block::default()
+                            .title("Original Price")
+                            .borders(Borders::ALL);
+                        let mut discount_block = Block::default()
+                            .title("Coupon/Discount")
+                            .borders(Borders::ALL);
+                        let mut tax_rate_block =
+                            Block::default().title("Tax %").borders(Borders::ALL);
+                        let mut currency_block = Block::default()
+                            .title(format!(
+                                "Currency (Optional, {} if blank)",
+                                currency::home()
+                            ))
+                            .borders(Borders::ALL);
+                        let mut store_block = Block::default()
+                            .title("Store (Optional, Right to complete)")
+                            .borders(Borders::ALL);
+                        let mut expended_block = Block::default()
+                            .title("Expended Date (Optional)")
+                            .borders(Borders::ALL);
+                        let mut paid_by_block = Block::default()
+                            .title("Paid By (Optional)")
+                            .borders(Borders::ALL);
+                        let mut split_block = Block::default()
+                            .title("Split Between (Optional, comma separated)")
+                            .borders(Borders::ALL);
+
+                        match editing {
+                            ItemInfo::Ingredient => {
+                                ingredient_block = ingredient_block.style(active_style)
+                            }
+                            ItemInfo::Price => price_block = price_block.style(active_style),
+                            ItemInfo::OriginalPrice => {
+                                original_price_block = original_price_block.style(active_style)
+                            }
+                            ItemInfo::Discount => {
+                                discount_block = discount_block.style(active_style)
+                            }
+                            ItemInfo::TaxRate => {
+                                tax_rate_block = tax_rate_block.style(active_style)
+                            }
+                            ItemInfo::Currency => {
+                                currency_block = currency_block.style(active_style)
+                            }
+                            ItemInfo::Store => store_block = store_block.style(active_style),
+                            ItemInfo::ExpendedDate => {
+                                expended_block = expended_block.style(active_style)
+                            }
+                            ItemInfo::PaidBy => paid_by_block = paid_by_block.style(active_style),
+                            ItemInfo::SplitBetween => split_block = split_block.style(active_style),
+                            _ => {}
+                        };
+
+                        let ingredient_text =
+                            Paragraph::new(app.ingredient_input.clone()).block(ingredient_block);
+                        frame.render_widget(ingredient_text, popup_chunks[0]);
+
+                        let price_text = Paragraph::new(app.price_input.clone()).block(price_block);
+                        frame.render_widget(price_text, popup_chunks[1]);
+
+                        // Optional pricing details share a row, the price above is what the receipt charged for the item
+                        let [original_price_area, discount_area, tax_rate_area] =
+                            Layout::horizontal([Constraint::Fill(1); 3]).areas(popup_chunks[2]);
+                        frame.render_widget(
+                            Paragraph::new(app.original_price_input.clone())
+                                .block(original_price_block),
+                            original_price_area,
+                        );
+                        frame.render_widget(
+                            Paragraph::new(app.discount_input.clone()).block(discount_block),
+                            discount_area,
+                        );
+                        frame.render_widget(
+                            Paragraph::new(app.tax_rate_input.clone()).block(tax_rate_block),
+                            tax_rate_area,
+                        );
+
+                        frame.render_widget(
+                            Paragraph::new(app.currency_input.clone()).block(currency_block),
+                            popup_chunks[3],
+                        );
+
+                        // Show the rest of the suggested store dimmed after what has been typed
+                        let mut store_line = Line::from(app.store_input.clone());
+                        if let Some(rest) = app.store_suggestion().and_then(|store| {
+                            store
+                                .get(app.store_input.trim().len()..)
+                                .map(str::to_string)
+                        }) {
+                            store_line.push_span(Span::from(rest).dark_gray());
                         }
+                        frame.render_widget(
+                            Paragraph::new(store_line).block(store_block),
+                            popup_chunks[4],
+                        );
+
+                        let expended_text =
+                            Paragraph::new(app.expended_date_input.clone()).block(expended_block);
+                        frame.render_widget(expended_text, popup_chunks[5]);
+
+                        frame.render_widget(
+                            Paragraph::new(app.paid_by_input.clone()).block(paid_by_block),
+                            popup_chunks[6],
+                        );
+                        frame.render_widget(
+                            Paragraph::new(app.split_between_input.clone()).block(split_block),
+                            popup_chunks[7],
+                        );
                     }
                 }
-                CurrentScreen::EditExpended => {
-                    let popup_block = Block::default()
-                        .borders(Borders::NONE)
-                        .style(Style::default());
-
-                    let area = popup_area(frame.area(), 30, 6);
-                    frame.render_widget(Clear, area);
-                    frame.render_widget(popup_block, area);
-
-                    let popup_chunks = Layout::vertical([Constraint::Fill(1)])
-                        .margin(1)
-                        .split(area);
-
-                    let purchase_block = Block::default().title("Edit Expended Date").borders(Borders::ALL).style(active_style);
-                    let purchase_text = Paragraph::new(app.expended_date_input.clone()).block(purchase_block);
-                    frame.render_widget(purchase_text, popup_chunks[0]);
-                }
-                _ => {}
+            }
+            CurrentScreen::EditExpended => {
+                let popup_block = Block::default()
+                    .borders(Borders::NONE)
+                    .style(Style::default());
+
+                let area = popup_area(frame.area(), 30, 6);
+                frame.render_widget(Clear, area);
+                frame.render_widget(popup_block, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                    .margin(1)
+                    .split(area);
+
+                let purchase_block = Block::default()
+                    .title("Edit Expended Date")
+                    .borders(Borders::ALL)
+                    .style(active_style);
+                let purchase_text =
+                    Paragraph::new(app.expended_date_input.clone()).block(purchase_block);
+                frame.render_widget(purchase_text, popup_chunks[0]);
+            }
+            CurrentScreen::DiningInput => {
+                let popup_block = Block::default()
+                    .borders(Borders::NONE)
+                    .style(Style::default());
+
+                let area = popup_area(frame.area(), 30, 14);
+                frame.render_widget(Clear, area);
+                frame.render_widget(popup_block, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1); 3])
+                    .margin(1)
+                    .split(area);
+
+                let mut location_block = Block::default().title("Location").borders(Borders::ALL);
+                let mut amount_block = Block::default()
+                    .title("Dining Dollars Spent")
+                    .borders(Borders::ALL);
+                let mut date_block = Block::default()
+                    .title("Date (Optional)")
+                    .borders(Borders::ALL);
+
+                match editing {
+                    ItemInfo::Location => location_block = location_block.style(active_style),
+                    ItemInfo::Amount => amount_block = amount_block.style(active_style),
+                    ItemInfo::TransactionDate => date_block = date_block.style(active_style),
+                    _ => {}
+                };
+
+                frame.render_widget(
+                    Paragraph::new(app.location_input.clone()).block(location_block),
+                    popup_chunks[0],
+                );
+                frame.render_widget(
+                    Paragraph::new(app.amount_input.clone()).block(amount_block),
+                    popup_chunks[1],
+                );
+                frame.render_widget(
+                    Paragraph::new(app.transaction_date_input.clone()).block(date_block),
+                    popup_chunks[2],
+                );
+            }
+            CurrentScreen::TemplateInput => {
+                let area = popup_area(frame.area(), 30, 14);
+                frame.render_widget(Clear, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1); 3])
+                    .margin(1)
+                    .split(area);
+
+                let mut name_block = Block::default()
+                    .title("Template Name")
+                    .borders(Borders::ALL);
+                let mut items_block = Block::default()
+                    .title("Ingredients (comma separated)")
+                    .borders(Borders::ALL);
+                let mut every_block = Block::default()
+                    .title("Repeat Every N Days (Optional)")
+                    .borders(Borders::ALL);
+
+                match editing {
+                    ItemInfo::TemplateName => name_block = name_block.style(active_style),
+                    ItemInfo::TemplateItems => items_block = items_block.style(active_style),
+                    ItemInfo::TemplateEvery => every_block = every_block.style(active_style),
+                    _ => {}
+                };
+
+                frame.render_widget(
+                    Paragraph::new(app.template_name_input.clone()).block(name_block),
+                    popup_chunks[0],
+                );
+                frame.render_widget(
+                    Paragraph::new(app.template_items_input.clone()).block(items_block),
+                    popup_chunks[1],
+                );
+                frame.render_widget(
+                    Paragraph::new(app.template_every_input.clone()).block(every_block),
+                    popup_chunks[2],
+                );
+            }
+            CurrentScreen::ShoppingInput => {
+                let area = popup_area(frame.area(), 30, 6);
+                frame.render_widget(Clear, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                    .margin(1)
+                    .split(area);
+
+                let item_block = Block::default()
+                    .title("Add Item")
+                    .borders(Borders::ALL)
+                    .style(active_style);
+                let item_text = Paragraph::new(app.shopping_input.clone()).block(item_block);
+                frame.render_widget(item_text, popup_chunks[0]);
+            }
+            CurrentScreen::ViewInput => {
+                let area = popup_area(frame.area(), 30, 6);
+                frame.render_widget(Clear, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                    .margin(1)
+                    .split(area);
+
+                let name_block = Block::default()
+                    .title("Save Current Search As")
+                    .borders(Borders::ALL)
+                    .style(active_style);
+                let name_text = Paragraph::new(app.view_input.clone()).block(name_block);
+                frame.render_widget(name_text, popup_chunks[0]);
+            }
+            CurrentScreen::ProfileInput => {
+                let area = popup_area(frame.area(), 30, 6);
+                frame.render_widget(Clear, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                    .margin(1)
+                    .split(area);
+
+                let name_block = Block::default()
+                    .title("New Profile")
+                    .borders(Borders::ALL)
+                    .style(active_style);
+                let name_text = Paragraph::new(app.profile_input.clone()).block(name_block);
+                frame.render_widget(name_text, popup_chunks[0]);
+            }
+            CurrentScreen::DiningBalance => {
+                let area = popup_area(frame.area(), 30, 6);
+                frame.render_widget(Clear, area);
+
+                let popup_chunks = Layout::vertical([Constraint::Fill(1)])
+                    .margin(1)
+                    .split(area);
+
+                let balance_block = Block::default()
+                    .title("Starting Dining Dollars")
+                    .borders(Borders::ALL)
+                    .style(active_style);
+                let balance_text =
+                    Paragraph::new(app.dining_balance_input.clone()).block(balance_block);
+                frame.render_widget(balance_text, popup_chunks[0]);
+            }
+            _ => {}
         }
     }
+
+    match &app.current_screen {
+        CurrentScreen::Analysis => render_analysis(frame, app),
+        CurrentScreen::Templates => render_templates(frame, app),
+        CurrentScreen::RecurringConfirm => render_recurring_confirm(frame, app),
+        CurrentScreen::ShoppingList => render_shopping_list(frame, app),
+        CurrentScreen::ImportPreview => render_import_preview(frame, app),
+        CurrentScreen::AuditLog => render_audit_log(frame, app),
+        CurrentScreen::SettleUp => render_settle_up(frame),
+        CurrentScreen::Profiles => render_profiles(frame, app),
+        CurrentScreen::Views => render_views(frame, app),
+        CurrentScreen::Columns => render_columns(frame, app),
+        CurrentScreen::StorePrices => render_store_prices(frame),
+        _ => {}
+    }
 }
 
-fn popup_area(area: Rect, percent_x: u16, pixel_y: u16) -> Rect {
-    let vertical = Layout::vertical([Constraint::Length(pixel_y)]).flex(Flex::Center);
-    let horizontal = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center);
-    let [area] = vertical.areas(area);
-    let [area] = horizontal.areas(area);
-    area
+fn render_templates(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 50, 16);
+    frame.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = app
+        .templates
+        .iter()
+        .map(|template| {
+            let schedule = match template.every_days {
+                Some(days) => format!(" (every {} days)", days),
+                None => String::new(),
+            };
+            ListItem::new(format!(
+                "{}{}: {}",
+                template.name,
+                schedule,
+                template.ingredients.join(", ")
+            ))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title("Templates")
+                .title_bottom(" (Enter) add items | (n) new | (x) delete | (Esc) back "),
+        )
+        .style(Style::new().light_blue())
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(list, area, &mut app.template_state);
 }
 
-fn render_table (rows: Vec<Row>, query_input: String) -> Table {
-    // Columns widths are constrained in the same way as Layout...
+fn render_store_prices(frame: &mut Frame) {
+    let area = popup_area(frame.area(), 70, 20);
+    frame.render_widget(Clear, area);
+
+    let rows: Vec<Row> = App::get_store_prices()
+        .into_iter()
+        .map(|price| {
+            let style = if price.cheapest {
+                Style::new().green()
+            } else {
+                Style::new()
+            };
+            Row::new(vec![
+                price.ingredient,
+                price.store,
+                price.average_price.to_string(),
+                price.purchases.to_string(),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(2),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(7),
+        ],
+    )
+    .header(Row::new(vec!["Ingredient", "Store", "Average", "Bought"]).style(Style::new().bold()))
+    .block(
+        Block::bordered()
+            .title("Average Price by Store")
+            .title_bottom(" cheapest in green | (Esc) back "),
+    )
+    .style(Style::new().light_blue());
+
+    frame.render_widget(table, area);
+}
+
+fn render_columns(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 50, 15);
+    frame.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = app
+        .columns
+        .columns
+        .iter()
+        .map(|setting| {
+            let check = if setting.visible { "[x]" } else { "[ ]" };
+            let width = match setting.width {
+                0 => "fill".to_string(),
+                width => width.to_string(),
+            };
+            ListItem::new(format!(
+                "{} {:<16} {}",
+                check,
+                setting.column.title(),
+                width
+            ))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title("Columns")
+                .title_bottom(" (Space) show/hide | (+/-) width | (K/J) move | (Esc) done "),
+        )
+        .style(Style::new().light_blue())
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(list, area, &mut app.column_state);
+}
+
+fn render_views(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 50, 14);
+    frame.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = app
+        .views
+        .iter()
+        .map(|view| {
+            let search = if view.search.is_empty() {
+                "everything"
+            } else {
+                view.search.as_str()
+            };
+            ListItem::new(format!("{}: {} by {}", view.name, search, view.sort))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title("Views")
+                .title_bottom(" (Enter) switch | (n) save current | (x) delete | (Esc) back "),
+        )
+        .style(Style::new().light_blue())
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(list, area, &mut app.view_state);
+}
+
+fn render_profiles(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 40, 12);
+    frame.render_widget(Clear, area);
+
+    let current = db::profile();
+    let items: Vec<ListItem> = app
+        .profiles
+        .iter()
+        .map(|name| {
+            let marker = if *name == current { " (current)" } else { "" };
+            ListItem::new(format!("{}{}", name, marker))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title("Profiles")
+                .title_bottom(" (Enter) switch | (n) new | (Esc) back "),
+        )
+        .style(Style::new().light_blue())
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(list, area, &mut app.profile_state);
+}
+
+fn render_shopping_list(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 40, 20);
+    frame.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = app
+        .shopping_list
+        .iter()
+        .map(|item| ListItem::new(format!("{} ({})", item.ingredient, item.price_text())))
+        .collect();
+
+    let total: Money = app
+        .shopping_list
+        .iter()
+        .filter_map(|item| item.home_price())
+        .sum();
+
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title(format!("Shopping List (est. {})", total))
+                .title_bottom(
+                    " (n) add | (x) remove | (r) regenerate | (c) mark bought | (Esc) back ",
+                ),
+        )
+        .style(Style::new().light_blue())
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(list, area, &mut app.shopping_state);
+}
+
+fn render_import_preview(frame: &mut Frame, app: &App) {
+    let area = popup_area(frame.area(), 80, frame.area().height.saturating_sub(4));
+    frame.render_widget(Clear, area);
+
+    let skipped = app.import_rows.iter().filter(|row| row.skipped()).count();
+
+    let rows: Vec<Row> = app
+        .import_rows
+        .iter()
+        .map(|row| {
+            let style = if row.skipped() {
+                Style::new().dark_gray().crossed_out()
+            } else {
+                Style::new().fg(tailwind::SLATE.c200)
+            };
+
+            Row::new(vec![
+                row.ingredient.clone(),
+                row.price.to_string(),
+                row.purchase_date.clone(),
+                row.expended_date.clone(),
+                if row.credit {
+                    "credit".to_string()
+                } else if row.duplicate {
+                    "duplicate".to_string()
+                } else {
+                    String::new()
+                },
+            ])
+            .style(style)
+        })
+        .collect();
+
     let widths = [
-        Constraint::Length(12),
+        Constraint::Fill(2),
         Constraint::Fill(1),
         Constraint::Fill(1),
         Constraint::Fill(1),
+        Constraint::Length(10),
+    ];
+
+    let table = Table::new(rows, widths)
+        .style(Style::new().light_blue())
+        .header(
+            Row::new(vec![
+                "Ingredient",
+                "Price",
+                "Purchase Date",
+                "Expended Date",
+                "",
+            ])
+            .style(Style::new().bold())
+            .bottom_margin(1),
+        )
+        .block(
+            Block::bordered()
+                .title(format!(
+                    "Import Preview ({} new, {} duplicates or credits)",
+                    app.import_rows.len() - skipped,
+                    skipped
+                ))
+                .title_bottom(" (Enter) import | (Esc) cancel "),
+        );
+
+    frame.render_widget(table, area);
+}
+
+fn render_audit_log(frame: &mut Frame, app: &mut App) {
+    let area = popup_area(frame.area(), 90, frame.area().height.saturating_sub(4));
+    frame.render_widget(Clear, area);
+
+    let rows: Vec<Row> = app
+        .audit_entries
+        .iter()
+        .map(|entry| {
+            Row::new(vec![
+                entry.changed_at.clone(),
+                match &entry.table {
+                    Some(table) => format!("{} {}", table, entry.purchase_id),
+                    None => entry.purchase_id.to_string(),
+                },
+                entry.operation.clone(),
+                entry.before.clone().unwrap_or_default(),
+                entry.after.clone().unwrap_or_default(),
+            ])
+            .style(Style::new().fg(tailwind::SLATE.c200))
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(19),
+        Constraint::Length(22),
+        Constraint::Length(7),
+        Constraint::Fill(1),
         Constraint::Fill(1),
+    ];
+
+    let table = Table::new(rows, widths)
+        .style(Style::new().light_blue())
+        .header(
+            Row::new(vec!["Changed At", "ID", "Change", "Before", "After"])
+                .style(Style::new().bold())
+                .bottom_margin(1),
+        )
+        .block(
+            Block::bordered()
+                .title("Audit Log")
+                .title_bottom(" (Esc) back "),
+        )
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(table, area, &mut app.audit_state);
+}
+
+fn render_settle_up(frame: &mut Frame) {
+    let settle_up = settle::settle_up();
+
+    let area = popup_area(frame.area(), 40, 20);
+    frame.render_widget(Clear, area);
+
+    let mut settle_text = vec![Line::from("Balances".bold())];
+    for (person, balance) in &settle_up.balances {
+        let text = balance.to_string();
+        settle_text.push(Line::from(vec![
+            format!("{}: ", person).into(),
+            if *balance < Money::ZERO {
+                text.red()
+            } else {
+                text.green()
+            },
+        ]));
+    }
+
+    settle_text.push(Line::from(""));
+    settle_text.push(Line::from("To Settle Up".bold()));
+    if settle_up.transfers.is_empty() {
+        settle_text.push(Line::from("Everyone is even"));
+    }
+    for (from, to, amount) in &settle_up.transfers {
+        settle_text.push(Line::from(format!("{} pays {} {}", from, to, amount)));
+    }
+
+    frame.render_widget(
+        Paragraph::new(settle_text)
+            .block(
+                Block::bordered()
+                    .title("Settle Up")
+                    .title_bottom(" (Esc) back "),
+            )
+            .centered()
+            .blue(),
+        area,
+    );
+}
+
+fn render_recurring_confirm(frame: &mut Frame, app: &App) {
+    if let Some(template) = app.due_templates.first() {
+        let area = popup_area(frame.area(), 40, 7);
+        frame.render_widget(Clear, area);
+
+        let confirm_text = vec![
+            Line::from(format!("\"{}\" is due", template.name).bold()),
+            Line::from(template.ingredients.join(", ")),
+            Line::from("(y) add items | (n) skip | (Esc) ask later"),
+        ];
+
+        frame.render_widget(
+            Paragraph::new(confirm_text)
+                .block(Block::bordered().title("Recurring Purchase"))
+                .centered()
+                .blue(),
+            area,
+        );
+    }
+}
+
+fn render_analysis(frame: &mut Frame, app: &App) {
+    let analysis = App::get_break_even_analysis();
+
+    let area = popup_area(frame.area(), 50, 14);
+    frame.render_widget(Clear, area);
 
+    let [text_area, input_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(area);
+
+    let mut analysis_text = vec![
+        Line::from(vec![
+            "Semester Grocery Spend: ".into(),
+            analysis.grocery_spend.to_string().red(),
+        ]),
+        Line::from(vec![
+            "Home-Cooked Meals Recorded: ".into(),
+            format!("{}", analysis.home_meals).red(),
+        ]),
+        Line::from(vec![
+            "Cost Per Swipe: ".into(),
+            analysis.swipe_price.to_string().red(),
+        ]),
     ];
 
+    match (analysis.cost_per_home_meal, analysis.savings_per_meal) {
+        (Some(cost), Some(savings)) => {
+            analysis_text.insert(
+                2,
+                Line::from(vec![
+                    "Cost Per Home-Cooked Meal: ".into(),
+                    cost.to_string().red(),
+                ]),
+            );
+            analysis_text.push(if savings > Money::ZERO {
+                Line::from(format!("Cooking saves {} per meal", savings).green())
+            } else {
+                Line::from(format!("Swiping saves {} per meal", -savings).red())
+            });
+        }
+        _ => analysis_text.push(Line::from(
+            "Record home-cooked servings below to compare costs",
+        )),
+    }
+
+    analysis_text.push(match analysis.swipes_to_drop {
+        Some(swipes) => Line::from(format!(
+            "Drop {} swipes next semester to save {}",
+            swipes, analysis.drop_savings
+        )),
+        None => Line::from("Record swipes with (w) to project swipes to drop"),
+    });
+    if let Some(swap_savings) = analysis.weekly_swap_savings {
+        analysis_text.push(Line::from(format!(
+            "Cooking one swiped meal a week instead: {}/semester",
+            swap_savings
+        )));
+    }
+
+    frame.render_widget(
+        Paragraph::new(analysis_text)
+            .block(Block::bordered().title("Is the Meal Plan Worth It?"))
+            .centered()
+            .blue(),
+        text_area,
+    );
+
+    let servings_block = Block::default()
+        .title("Record Home-Cooked Servings (Enter)")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::LightBlue).fg(Color::Black));
+    frame.render_widget(
+        Paragraph::new(app.servings_input.clone()).block(servings_block),
+        input_area,
+    );
+}
+
+fn popup_area(area: Rect, percent_x: u16, pixel_y: u16) -> Rect {
+    let vertical = Layout::vertical([Constraint::Length(pixel_y)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center);
+    let [area] = vertical.areas(area);
+    let [area] = horizontal.areas(area);
+    area
+}
+
+// Styles the characters of `text` matched by any of the search terms
+fn highlight(text: &str, terms: &[String]) -> Line<'static> {
+    let matched: HashSet<usize> = terms
+        .iter()
+        .filter_map(|term| fuzzy_match(term, text))
+        .flat_map(|found| found.positions)
+        .collect();
+    if matched.is_empty() {
+        return Line::from(text.to_string());
+    }
+
+    let spans: Vec<Span> = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if matched.contains(&i) {
+                Span::from(c.to_string()).yellow().bold()
+            } else {
+                Span::from(c.to_string())
+            }
+        })
+        .collect();
+    Line::from(spans)
+}
+
+fn render_table<'a>(rows: Vec<Row<'a>>, columns: &[ColumnSetting], sort: &Sort) -> Table<'a> {
+    // Columns widths are constrained in the same way as Layout...
+    let widths: Vec<Constraint> = columns
+        .iter()
+        .map(|setting| match setting.width {
+            0 => Constraint::Fill(1),
+            width => Constraint::Length(width),
+        })
+        .collect();
+
     let bar = " █ ";
 
     Table::new(rows, widths)
@@ -236,14 +1051,11 @@ fn render_table (rows: Vec<Row>, query_input: String) -> Table {
         .style(Style::new().light_blue())
         // It has an optional header, which is simply a Row always visible at the top.
         .header(
-            Row::new(vec!["ID", "Ingredient", "Price", "Purchase Date", "Expended Date"])
+            Row::new(columns.iter().map(|setting| sort.header(setting.column)))
                 .style(Style::new().bold())
                 // To add space between the header and the rest of the rows, specify the margin
                 .bottom_margin(1),
         )
-        .footer((Row::new(vec![format!("Search: {}", query_input)])))
-        // As any other widget, a Table can be wrapped in a Block.
-        .block(Block::new().title("Transactions").borders(Borders::ALL))
         // The selected row and its content can also be styled.
         .highlight_style(Style::new().reversed())
         // ...and potentially show a symbol in front of the selection.
@@ -254,6 +1066,4 @@ fn render_table (rows: Vec<Row>, query_input: String) -> Table {
             "".into(),
         ]))
         .highlight_spacing(HighlightSpacing::Always)
-
-
-}
\ No newline at end of file
+}
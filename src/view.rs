@@ -0,0 +1,82 @@
+use sqlite::{Connection, State};
+
+use crate::db;
+use crate::sort::Sort;
+
+/// A saved search and sort, applied together from the views list
+#[derive(Clone)]
+pub struct View {
+    pub name: String,
+    /// Search in the query syntax, see `query::parse`
+    pub search: String,
+    pub sort: Sort,
+}
+
+/// Views added the first time a database is opened, deleting one keeps it deleted
+const DEFAULT_VIEWS: [(&str, &str, &str); 3] = [
+    ("Pantry", "unexpended", "purchaseDate ASC"),
+    ("This month", "after:month", "purchaseDate DESC"),
+    ("Expensive items", "price>=10", "price DESC"),
+];
+
+pub fn seed_defaults(conn: &Connection) {
+    let mut statement = conn
+        .prepare("SELECT 1 FROM setting WHERE key = 'views_seeded'")
+        .unwrap();
+    if let State::Row = statement.next().unwrap() {
+        return;
+    }
+
+    for (name, search, order_by) in DEFAULT_VIEWS {
+        let mut statement = conn
+            .prepare("INSERT OR IGNORE INTO view (name, search, orderBy) VALUES (?, ?, ?)")
+            .unwrap();
+        statement.bind((1, name)).unwrap();
+        statement.bind((2, search)).unwrap();
+        statement.bind((3, order_by)).unwrap();
+        statement.next().unwrap();
+    }
+    conn.execute("INSERT INTO setting (key, value) VALUES ('views_seeded', '1')")
+        .unwrap();
+}
+
+pub fn all() -> Vec<View> {
+    let conn = db::open();
+    let mut statement = conn.prepare("SELECT * FROM view ORDER BY name").unwrap();
+    let mut views = Vec::<View>::new();
+
+    while let State::Row = statement.next().unwrap() {
+        views.push(View {
+            name: statement.read::<String, _>("name").unwrap(),
+            search: statement.read::<String, _>("search").unwrap(),
+            sort: Sort::parse(&statement.read::<String, _>("orderBy").unwrap()).unwrap_or_default(),
+        });
+    }
+
+    views
+}
+
+pub fn get(name: &str) -> Option<View> {
+    all()
+        .into_iter()
+        .find(|view| view.name.eq_ignore_ascii_case(name))
+}
+
+// Saves a view, replacing any view with the same name
+pub fn save(view: &View) {
+    let conn = db::open();
+    let mut statement = conn
+        .prepare("INSERT OR REPLACE INTO view (name, search, orderBy) VALUES (?, ?, ?)")
+        .unwrap();
+    statement.bind((1, view.name.as_str())).unwrap();
+    statement.bind((2, view.search.as_str())).unwrap();
+    statement.bind((3, view.sort.to_string().as_str())).unwrap();
+    statement.next().unwrap();
+}
+
+pub fn delete(name: &str) {
+    let conn = db::open();
+    let mut statement = conn.prepare("DELETE FROM view WHERE name = ?").unwrap();
+    statement.bind((1, name)).unwrap();
+    statement.next().unwrap();
+}